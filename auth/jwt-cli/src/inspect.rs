@@ -0,0 +1,37 @@
+use std::io::{stdout, BufWriter};
+
+use anyhow::{bail, Context};
+use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+use clap::{ArgMatches, Command};
+use serde_json::{json, Value};
+
+use crate::args;
+
+pub static NAME: &str = "inspect";
+
+pub fn cmd() -> Command {
+    Command::new(NAME)
+        .about("Prints the header and claims of a JWT without verifying it")
+        .arg(args::token())
+}
+
+pub async fn exec(matches: &ArgMatches) -> anyhow::Result<()> {
+    let token = crate::token(matches)?;
+    let parts = token.split('.').collect::<Vec<_>>();
+    let [header, claims, signature] = parts.as_slice() else {
+        bail!("Expected JWT with 3 parts but found {}", parts.len());
+    };
+
+    let value = json!({
+        "header": decode_part(header).context("Failed to decode JWT header")?,
+        "claims": decode_part(claims).context("Failed to decode JWT claims")?,
+        "signed": !signature.is_empty(),
+    });
+    serde_json::to_writer_pretty(BufWriter::new(stdout()), &value)
+        .context("Failed to write JWT JSON")
+}
+
+fn decode_part(part: &str) -> anyhow::Result<Value> {
+    let data = URL_SAFE_NO_PAD.decode(part).context("Invalid base64url")?;
+    serde_json::from_slice(&data).context("Invalid JSON")
+}
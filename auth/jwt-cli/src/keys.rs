@@ -0,0 +1,26 @@
+use std::{
+    io::{stdout, BufWriter},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{ArgMatches, Command};
+
+use crate::args;
+
+pub static NAME: &str = "keys";
+
+pub fn cmd() -> Command {
+    Command::new(NAME)
+        .about("Lists the keys loaded from the JWKS URLs of a JwtDecoder config")
+        .arg(args::config())
+}
+
+pub async fn exec(matches: &ArgMatches) -> anyhow::Result<()> {
+    let config = matches.get_one::<PathBuf>(args::CONFIG).unwrap();
+    let jwt_decoder = crate::jwt_decoder(config)?;
+
+    let keys = jwt_decoder.keys().await;
+    serde_json::to_writer_pretty(BufWriter::new(stdout()), &keys)
+        .context("Failed to write keys JSON")
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs::read_to_string,
+    io::{read_to_string as read_all, stdin},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use appbiotic_auth_jwt_decoder::{config, tokio::JwtDecoder};
+use clap::{command, ArgMatches, Command};
+
+pub mod inspect;
+pub mod keys;
+pub mod mint;
+pub mod verify;
+
+pub fn cli() -> Command {
+    command!()
+        .about("Inspects, verifies and mints JWTs for debugging auth")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(inspect::cmd())
+        .subcommand(keys::cmd())
+        .subcommand(mint::cmd())
+        .subcommand(verify::cmd())
+}
+
+pub async fn exec(matches: &ArgMatches) -> anyhow::Result<()> {
+    if let Some(matches) = matches.subcommand_matches(inspect::NAME) {
+        inspect::exec(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(keys::NAME) {
+        keys::exec(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(mint::NAME) {
+        mint::exec(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(verify::NAME) {
+        verify::exec(matches).await
+    } else {
+        bail!("Expected valid subcommand");
+    }
+}
+
+/// Returns the token argument, or reads it from stdin when absent or `-`.
+fn token(matches: &ArgMatches) -> anyhow::Result<String> {
+    let token = match matches.get_one::<String>(args::TOKEN) {
+        Some(token) if token != "-" => token.to_owned(),
+        _ => read_all(stdin()).context("Failed to read token from stdin")?,
+    };
+    Ok(token.trim().to_owned())
+}
+
+/// Loads a [config::JwtDecoder] from a YAML (`.yaml`, `.yml`) or JSON file and builds a tokio
+/// decoder from it regardless of the configured kind.
+fn jwt_decoder(path: &Path) -> anyhow::Result<JwtDecoder> {
+    let content =
+        read_to_string(path).with_context(|| format!("Failed to read config file: {path:?}"))?;
+    let mut config: config::JwtDecoder = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse YAML config file: {path:?}"))?,
+        _ => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse JSON config file: {path:?}"))?,
+    };
+    config.kind = config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {});
+    JwtDecoder::new(config).context("Failed to create JWT decoder")
+}
+
+pub mod args {
+    use clap::{builder::PathBufValueParser, Arg};
+
+    pub static CONFIG: &str = "config";
+    pub static TOKEN: &str = "token";
+
+    pub fn config() -> Arg {
+        Arg::new(CONFIG)
+            .help("Path to a JwtDecoder config as JSON or YAML")
+            .long(CONFIG)
+            .value_name("FILE")
+            .value_parser(PathBufValueParser::new())
+            .env("APPBIOTIC_JWT_DECODER_CONFIG")
+            .required(true)
+    }
+
+    pub fn token() -> Arg {
+        Arg::new(TOKEN)
+            .help("The JWT, read from stdin if omitted or `-`")
+            .value_name("TOKEN")
+    }
+}
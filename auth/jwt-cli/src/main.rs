@@ -0,0 +1,18 @@
+use std::process::ExitCode;
+
+async fn execute() -> anyhow::Result<()> {
+    let cmd = appbiotic_auth_jwt_cli::cli();
+    let matches = cmd.get_matches();
+    appbiotic_auth_jwt_cli::exec(&matches).await
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match execute().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
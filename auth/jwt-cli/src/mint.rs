@@ -0,0 +1,126 @@
+use std::{
+    fs::read,
+    io::{stdout, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use clap::{ArgMatches, Command};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::Value;
+
+pub static NAME: &str = "mint";
+
+pub fn cmd() -> Command {
+    Command::new(NAME)
+        .about("Mints a test JWT signed with a local key")
+        .arg(args::algorithm())
+        .arg(args::key())
+        .arg(args::kid())
+        .arg(args::claims())
+        .arg(args::expires_in())
+}
+
+pub async fn exec(matches: &ArgMatches) -> anyhow::Result<()> {
+    let algorithm = *matches.get_one::<Algorithm>(args::ALGORITHM).unwrap();
+    let key = matches.get_one::<PathBuf>(args::KEY).unwrap();
+    let kid = matches.get_one::<String>(args::KID);
+    let claims = matches.get_one::<String>(args::CLAIMS).unwrap();
+    let expires_in = matches.get_one::<u64>(args::EXPIRES_IN);
+
+    let key_data = read(key).with_context(|| format!("Failed to read key file: {key:?}"))?;
+    let encoding_key = encoding_key(algorithm, &key_data)
+        .with_context(|| format!("Failed to load {algorithm:?} key from {key:?}"))?;
+
+    let mut claims: Value = serde_json::from_str(claims).context("Failed to parse claims")?;
+    let Some(claims_object) = claims.as_object_mut() else {
+        bail!("Expected claims to be a JSON object");
+    };
+    if let Some(expires_in) = expires_in {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("System time is before the UNIX epoch")?
+            .as_secs();
+        claims_object.entry("iat").or_insert(now.into());
+        claims_object.insert("exp".to_owned(), (now + expires_in).into());
+    }
+
+    let mut header = Header::new(algorithm);
+    header.kid = kid.cloned();
+    let token = encode(&header, &claims, &encoding_key).context("Failed to sign JWT")?;
+    writeln!(stdout(), "{token}").context("Failed to write JWT")
+}
+
+fn encoding_key(algorithm: Algorithm, key: &[u8]) -> anyhow::Result<EncodingKey> {
+    Ok(match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => EncodingKey::from_secret(key),
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => EncodingKey::from_rsa_pem(key)?,
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(key)?,
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(key)?,
+    })
+}
+
+mod args {
+    use std::str::FromStr;
+
+    use clap::{
+        builder::{NonEmptyStringValueParser, PathBufValueParser},
+        value_parser, Arg,
+    };
+    use jsonwebtoken::Algorithm;
+
+    pub static ALGORITHM: &str = "algorithm";
+    pub static CLAIMS: &str = "claims";
+    pub static EXPIRES_IN: &str = "expires-in";
+    pub static KEY: &str = "key";
+    pub static KID: &str = "kid";
+
+    pub fn algorithm() -> Arg {
+        Arg::new(ALGORITHM)
+            .help("The signing algorithm, e.g. RS256, ES256, EdDSA or HS256")
+            .long(ALGORITHM)
+            .value_name("ALG")
+            .value_parser(Algorithm::from_str)
+            .required(true)
+    }
+
+    pub fn claims() -> Arg {
+        Arg::new(CLAIMS)
+            .help("The claims as a JSON object")
+            .long(CLAIMS)
+            .value_name("JSON")
+            .value_parser(NonEmptyStringValueParser::new())
+            .default_value("{}")
+    }
+
+    pub fn expires_in() -> Arg {
+        Arg::new(EXPIRES_IN)
+            .help("Sets `exp` this many seconds from now, and `iat` if not in the claims")
+            .long(EXPIRES_IN)
+            .value_name("SECONDS")
+            .value_parser(value_parser!(u64))
+    }
+
+    pub fn key() -> Arg {
+        Arg::new(KEY)
+            .help("Path to a PKCS#8 PEM private key, or the raw secret for HMAC algorithms")
+            .long(KEY)
+            .value_name("FILE")
+            .value_parser(PathBufValueParser::new())
+            .required(true)
+    }
+
+    pub fn kid() -> Arg {
+        Arg::new(KID)
+            .help("The key ID set in the JWT header")
+            .long(KID)
+            .value_name("KID")
+            .value_parser(NonEmptyStringValueParser::new())
+    }
+}
@@ -0,0 +1,38 @@
+use std::{
+    io::{stdout, BufWriter},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context};
+use appbiotic_auth_jwt_decoder::{error::JwtDecoderErrorReason, JwtDecode};
+use clap::{ArgMatches, Command};
+use serde_json::json;
+
+use crate::args;
+
+pub static NAME: &str = "verify";
+
+pub fn cmd() -> Command {
+    Command::new(NAME)
+        .about("Verifies a JWT against a JwtDecoder config")
+        .arg(args::config())
+        .arg(args::token())
+}
+
+pub async fn exec(matches: &ArgMatches) -> anyhow::Result<()> {
+    let config = matches.get_one::<PathBuf>(args::CONFIG).unwrap();
+    let token = crate::token(matches)?;
+    let jwt_decoder = crate::jwt_decoder(config)?;
+
+    let token_data = jwt_decoder
+        .decode(&token)
+        .await
+        .map_err(|err| anyhow!("{}: {err}", JwtDecoderErrorReason::from(&err).as_ref()))?;
+
+    let value = json!({
+        "header": token_data.header,
+        "claims": token_data.claims,
+    });
+    serde_json::to_writer_pretty(BufWriter::new(stdout()), &value)
+        .context("Failed to write JWT JSON")
+}
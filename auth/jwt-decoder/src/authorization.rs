@@ -0,0 +1,408 @@
+//! Authorization of decoded tokens against declared [Policy] requirements.
+//!
+//! ```
+//! use appbiotic_auth_jwt_decoder::authorization::Policy;
+//!
+//! let policy: Policy = serde_json::from_value(serde_json::json!({
+//!     "all_of": [
+//!         { "all_of_scopes": ["orders:read"] },
+//!         { "any_of": [
+//!             { "role": { "role": "admin" } },
+//!             { "role": { "role": "support", "resource": "orders" } },
+//!         ] },
+//!     ],
+//! }))
+//! .unwrap();
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use jsonwebtoken::TokenData;
+use serde_json::Value;
+
+use crate::{config, error::JwtDecoderError};
+
+/// Wildcard claim path segment matching any key of an object, which is taken as the resource the
+/// roles found below it apply to, e.g. `resource_access.*.roles`.
+pub const RESOURCE_WILDCARD: &str = "*";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Principal {
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    /// Union of the space-delimited `scope` claim and the `scp` claim.
+    pub scopes: BTreeSet<String>,
+    pub roles: BTreeSet<String>,
+    pub resource_roles: BTreeMap<String, BTreeSet<String>>,
+    pub claims: Value,
+}
+
+impl Principal {
+    pub fn new(token_data: &TokenData<Value>, config: &config::Principal) -> Self {
+        let claims = &token_data.claims;
+
+        let mut scopes = BTreeSet::new();
+        for claim in ["scope", "scp"] {
+            match claims.get(claim) {
+                Some(Value::String(value)) => {
+                    scopes.extend(value.split_whitespace().map(ToOwned::to_owned))
+                }
+                Some(Value::Array(values)) => scopes.extend(
+                    values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(ToOwned::to_owned),
+                ),
+                _ => {}
+            }
+        }
+
+        let mut roles = BTreeSet::new();
+        let mut resource_roles = BTreeMap::<String, BTreeSet<String>>::new();
+        for role_claim in &config.role_claims {
+            let segments = claim_path(role_claim);
+            for (resource, value) in claim_values(claims, &segments, None) {
+                let values = match value {
+                    Value::String(value) => vec![value.to_owned()],
+                    Value::Array(values) => values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(ToOwned::to_owned)
+                        .collect(),
+                    _ => continue,
+                };
+                match resource {
+                    Some(resource) => resource_roles
+                        .entry(resource.to_owned())
+                        .or_default()
+                        .extend(values),
+                    None => roles.extend(values),
+                }
+            }
+        }
+
+        Self {
+            subject: claim_string(claims, "sub"),
+            issuer: claim_string(claims, "iss"),
+            scopes,
+            roles,
+            resource_roles,
+            claims: claims.to_owned(),
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// Whether the principal has the role globally or, if given, for the resource.
+    pub fn has_role(&self, role: &str, resource: Option<&str>) -> bool {
+        self.roles.contains(role)
+            || resource
+                .and_then(|resource| self.resource_roles.get(resource))
+                .is_some_and(|roles| roles.contains(role))
+    }
+
+    /// Returns the claim at the dot-separated path, or the JSON pointer if it starts with `/`.
+    pub fn claim(&self, path: &str) -> Option<&Value> {
+        claim_path(path)
+            .iter()
+            .try_fold(&self.claims, |value, segment| value.get(segment.as_ref()))
+    }
+
+    pub fn authorize(&self, policy: &Policy) -> Result<(), JwtDecoderError> {
+        policy
+            .check(self)
+            .map_err(JwtDecoderError::new_permission_denied)
+    }
+}
+
+fn claim_string(claims: &Value, claim: &str) -> Option<String> {
+    claims
+        .get(claim)
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+}
+
+/// Splits a claim path into its segments. Paths starting with `/` are JSON pointers (RFC 6901),
+/// which address namespaced claims such as `/https:~1~1example.com~1roles`, and any other path is
+/// dot-separated, e.g. `realm_access.roles`.
+fn claim_path(path: &str) -> Vec<Cow<'_, str>> {
+    match path.strip_prefix('/') {
+        Some(pointer) => pointer
+            .split('/')
+            .map(|segment| match segment.contains('~') {
+                true => Cow::Owned(segment.replace("~1", "/").replace("~0", "~")),
+                false => Cow::Borrowed(segment),
+            })
+            .collect(),
+        None => path.split('.').map(Cow::Borrowed).collect(),
+    }
+}
+
+fn claim_values<'a>(
+    value: &'a Value,
+    segments: &[Cow<'_, str>],
+    resource: Option<&'a str>,
+) -> Vec<(Option<&'a str>, &'a Value)> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return vec![(resource, value)];
+    };
+    if segment == RESOURCE_WILDCARD {
+        return match value {
+            Value::Object(object) => object
+                .iter()
+                .flat_map(|(key, value)| claim_values(value, rest, Some(key)))
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+    match value.get(segment.as_ref()) {
+        Some(value) => claim_values(value, rest, resource),
+        None => Vec::new(),
+    }
+}
+
+/// A requirement on a [Principal], composable with [Policy::AllOf] and [Policy::AnyOf].
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    AllOf(Vec<Policy>),
+    AnyOf(Vec<Policy>),
+    AllOfScopes(Vec<String>),
+    AnyOfScopes(Vec<String>),
+    Role {
+        role: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resource: Option<String>,
+    },
+    Claim {
+        path: String,
+        predicate: ClaimPredicate,
+    },
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimPredicate {
+    Exists,
+    Equals(Value),
+    /// The claim is an array containing the value or a space-delimited string, such as `scope`,
+    /// with the string value as one of its tokens.
+    Contains(Value),
+    OneOf(Vec<Value>),
+}
+
+impl ClaimPredicate {
+    pub fn matches(&self, claim: Option<&Value>) -> bool {
+        let Some(claim) = claim else {
+            return false;
+        };
+        match self {
+            ClaimPredicate::Exists => true,
+            ClaimPredicate::Equals(value) => claim == value,
+            ClaimPredicate::Contains(value) => match (claim, value) {
+                (Value::Array(values), value) => values.contains(value),
+                (Value::String(claim), Value::String(value)) => {
+                    claim.split_whitespace().any(|token| token == value)
+                }
+                _ => false,
+            },
+            ClaimPredicate::OneOf(values) => values.contains(claim),
+        }
+    }
+}
+
+impl Policy {
+    pub fn all_of_scopes<I: IntoIterator<Item = S>, S: Into<String>>(scopes: I) -> Self {
+        Self::AllOfScopes(scopes.into_iter().map(Into::into).collect())
+    }
+
+    pub fn any_of_scopes<I: IntoIterator<Item = S>, S: Into<String>>(scopes: I) -> Self {
+        Self::AnyOfScopes(scopes.into_iter().map(Into::into).collect())
+    }
+
+    pub fn role(role: impl Into<String>, resource: Option<&str>) -> Self {
+        Self::Role {
+            role: role.into(),
+            resource: resource.map(ToOwned::to_owned),
+        }
+    }
+
+    pub fn claim(path: impl Into<String>, predicate: ClaimPredicate) -> Self {
+        Self::Claim {
+            path: path.into(),
+            predicate,
+        }
+    }
+
+    pub fn is_satisfied_by(&self, principal: &Principal) -> bool {
+        self.check(principal).is_ok()
+    }
+
+    /// Returns a description of the first unmet requirement. Empty lists are never met, so that
+    /// a policy templated with no requirements denies rather than grants access.
+    fn check(&self, principal: &Principal) -> Result<(), String> {
+        match self {
+            Policy::AllOf(policies) if policies.is_empty() => Err("Empty all_of".to_owned()),
+            Policy::AllOfScopes(scopes) if scopes.is_empty() => {
+                Err("Empty all_of_scopes".to_owned())
+            }
+            Policy::AllOf(policies) => policies
+                .iter()
+                .try_for_each(|policy| policy.check(principal)),
+            Policy::AnyOf(policies) => {
+                let mut unmet = Vec::new();
+                for policy in policies {
+                    match policy.check(principal) {
+                        Ok(()) => return Ok(()),
+                        Err(message) => unmet.push(message),
+                    }
+                }
+                Err(format!("None of: {}", unmet.join("; ")))
+            }
+            Policy::AllOfScopes(scopes) => {
+                let missing = scopes
+                    .iter()
+                    .filter(|scope| !principal.has_scope(scope))
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                if missing.is_empty() {
+                    Ok(())
+                } else {
+                    Err(format!("Missing scopes `{}`", missing.join(" ")))
+                }
+            }
+            Policy::AnyOfScopes(scopes) => {
+                if scopes.iter().any(|scope| principal.has_scope(scope)) {
+                    Ok(())
+                } else {
+                    Err(format!("Missing any of scopes `{}`", scopes.join(" ")))
+                }
+            }
+            Policy::Role { role, resource } => {
+                if principal.has_role(role, resource.as_deref()) {
+                    Ok(())
+                } else if let Some(resource) = resource {
+                    Err(format!("Missing role `{role}` for resource `{resource}`"))
+                } else {
+                    Err(format!("Missing role `{role}`"))
+                }
+            }
+            Policy::Claim { path, predicate } => {
+                if predicate.matches(principal.claim(path)) {
+                    Ok(())
+                } else {
+                    Err(format!("Claim `{path}` does not satisfy {predicate:?}"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use jsonwebtoken::{Header, TokenData};
+    use serde_json::json;
+
+    use crate::{config, error::JwtDecoderErrorReason};
+
+    use super::{ClaimPredicate, Policy, Principal};
+
+    #[test]
+    fn policies() {
+        let token_data = TokenData {
+            header: Header::default(),
+            claims: json!({
+                "sub": "user@example.com",
+                "iss": "an-issuer",
+                "scope": "orders:read orders:write",
+                "scp": ["profile"],
+                "roles": ["viewer"],
+                "resource_access": {
+                    "orders": { "roles": ["support"] },
+                    "billing": { "roles": ["admin"] },
+                },
+                "tenant": { "tier": "gold" },
+                "role": "not-admin",
+                "groups": "sysadmin-readonly staff",
+                "https://example.com/roles": ["auditor"],
+                "https://example.com/tenant": { "id": "t~1" },
+            }),
+        };
+        let principal = Principal::new(
+            &token_data,
+            &config::Principal {
+                role_claims: vec![
+                    "roles".to_owned(),
+                    "resource_access.*.roles".to_owned(),
+                    "/https:~1~1example.com~1roles".to_owned(),
+                ],
+            },
+        );
+
+        assert_eq!(principal.subject.as_deref(), Some("user@example.com"));
+        assert_eq!(principal.issuer.as_deref(), Some("an-issuer"));
+        assert!(principal.has_scope("orders:write"));
+        assert!(principal.has_scope("profile"));
+        assert!(principal.has_role("viewer", None));
+        assert!(principal.has_role("support", Some("orders")));
+        assert!(!principal.has_role("support", Some("billing")));
+        assert!(!principal.has_role("admin", None));
+        assert!(principal.has_role("auditor", None));
+
+        let policy = Policy::AllOf(vec![
+            Policy::all_of_scopes(["orders:read", "profile"]),
+            Policy::AnyOf(vec![
+                Policy::role("admin", Some("orders")),
+                Policy::role("support", Some("orders")),
+            ]),
+            Policy::claim("tenant.tier", ClaimPredicate::OneOf(vec![json!("gold")])),
+            Policy::claim("scope", ClaimPredicate::Contains(json!("orders:write"))),
+            Policy::claim("groups", ClaimPredicate::Contains(json!("staff"))),
+            Policy::claim(
+                "/https:~1~1example.com~1tenant/id",
+                ClaimPredicate::Equals(json!("t~1")),
+            ),
+        ]);
+        assert!(principal.authorize(&policy).is_ok());
+
+        for policy in [
+            Policy::any_of_scopes(["billing:read"]),
+            Policy::role("admin", Some("orders")),
+            Policy::claim("tenant.tier", ClaimPredicate::Equals(json!("silver"))),
+            Policy::claim("tenant.region", ClaimPredicate::Exists),
+            Policy::claim("role", ClaimPredicate::Contains(json!("admin"))),
+            Policy::claim("groups", ClaimPredicate::Contains(json!("admin"))),
+            Policy::claim("groups", ClaimPredicate::Contains(json!("sysadmin"))),
+            Policy::claim("scope", ClaimPredicate::Contains(json!("orders"))),
+            Policy::claim("scope", ClaimPredicate::Contains(json!(""))),
+            Policy::claim("https://example.com/roles", ClaimPredicate::Exists),
+            Policy::AllOf(vec![]),
+            Policy::AnyOf(vec![]),
+            Policy::all_of_scopes(Vec::<String>::new()),
+            Policy::any_of_scopes(Vec::<String>::new()),
+        ] {
+            assert_eq!(
+                principal
+                    .authorize(&policy)
+                    .err()
+                    .map(|e| JwtDecoderErrorReason::from(&e)),
+                Some(JwtDecoderErrorReason::PermissionDenied),
+                "{policy:?}"
+            );
+        }
+
+        let policy: Policy = serde_json::from_value(json!({
+            "any_of": [
+                { "all_of_scopes": ["orders:read"] },
+                { "claim": { "path": "sub", "predicate": { "equals": "nobody" } } },
+            ],
+        }))
+        .unwrap();
+        assert!(policy.is_satisfied_by(&principal));
+    }
+}
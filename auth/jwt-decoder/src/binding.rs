@@ -0,0 +1,93 @@
+//! Certificate-bound access token verification as described in
+//! [RFC 8705](https://www.rfc-editor.org/rfc/rfc8705#section-3.1).
+
+use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::error::JwtDecoderError;
+
+pub const CONFIRMATION_CLAIM: &str = "cnf";
+pub const X5T_S256_CONFIRMATION_METHOD: &str = "x5t#S256";
+
+/// Returns the base64url-encoded SHA-256 thumbprint of a DER encoded certificate.
+pub fn certificate_thumbprint(certificate_der: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(certificate_der))
+}
+
+/// Verifies that the `cnf.x5t#S256` claim, if present, matches the client certificate.
+///
+/// Tokens without a `cnf.x5t#S256` confirmation are not certificate-bound and pass regardless of
+/// whether a client certificate was presented.
+pub fn verify_certificate_binding(
+    claims: &serde_json::Value,
+    client_certificate: Option<&[u8]>,
+) -> Result<(), JwtDecoderError> {
+    let Some(confirmation) = claims.get(CONFIRMATION_CLAIM) else {
+        return Ok(());
+    };
+    let Some(expected) = confirmation.get(X5T_S256_CONFIRMATION_METHOD) else {
+        return Ok(());
+    };
+    let expected = expected.as_str().ok_or_else(|| {
+        JwtDecoderError::new_validation_failed(format!(
+            "Claim `{CONFIRMATION_CLAIM}.{X5T_S256_CONFIRMATION_METHOD}` must be a string"
+        ))
+    })?;
+    let expected = URL_SAFE_NO_PAD.decode(expected).map_err(|err| {
+        JwtDecoderError::new_validation_failed(format!(
+            "Claim `{CONFIRMATION_CLAIM}.{X5T_S256_CONFIRMATION_METHOD}` is not base64url: {err}"
+        ))
+    })?;
+
+    let client_certificate = client_certificate.ok_or_else(|| {
+        JwtDecoderError::new_certificate_binding_mismatch(
+            "Token is certificate-bound but no client certificate was presented".to_owned(),
+        )
+    })?;
+
+    if Sha256::digest(client_certificate).as_slice() != expected.as_slice() {
+        return Err(JwtDecoderError::new_certificate_binding_mismatch(
+            "Client certificate thumbprint does not match token confirmation".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::error::JwtDecoderError;
+
+    use super::{certificate_thumbprint, verify_certificate_binding};
+
+    #[test]
+    fn certificate_binding() {
+        let certificate = b"not really a certificate but hashed the same way";
+        let other_certificate = b"some other certificate";
+
+        let bound = json!({
+            "sub": "service@example.com",
+            "cnf": { "x5t#S256": certificate_thumbprint(certificate) },
+        });
+        let unbound = json!({ "sub": "service@example.com" });
+
+        assert!(verify_certificate_binding(&bound, Some(certificate)).is_ok());
+        assert!(verify_certificate_binding(&unbound, Some(certificate)).is_ok());
+        assert!(verify_certificate_binding(&unbound, None).is_ok());
+
+        assert!(matches!(
+            verify_certificate_binding(&bound, Some(other_certificate)),
+            Err(JwtDecoderError::CertificateBindingMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_certificate_binding(&bound, None),
+            Err(JwtDecoderError::CertificateBindingMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_certificate_binding(&json!({ "cnf": { "x5t#S256": 1 } }), Some(certificate)),
+            Err(JwtDecoderError::ValidationFailed { .. })
+        ));
+    }
+}
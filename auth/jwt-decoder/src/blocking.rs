@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Instant, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use jsonwebtoken::{jwk::JwkSet, TokenData};
+use tracing::info_span;
+use url::Url;
+
+use crate::{
+    config,
+    error::JwtDecoderError,
+    keys::{self, DecoderSettings, JwkInfo, JwksSourceStatus, Limits, VerificationKey},
+    metrics::{self, TokenFormat},
+    JwtDecodeBlocking,
+};
+
+type JwksResult = keys::JwksResult<Instant>;
+type State = keys::KeyState<Instant>;
+
+type Loaded = (Url, Result<JwkSet, JwtDecoderError>);
+
+/// Loads the JWKS at a URL, replaceable so that tests can control when loads complete.
+type Load = Arc<dyn Fn(&Url, &Limits) -> Result<JwkSet, JwtDecoderError> + Send + Sync>;
+
+/// A [JwtDecodeBlocking] implementation for callers without an async runtime. JWKS are fetched
+/// on short-lived threads so that [config::JwtDecoder::jwks_max_wait] is still honored. Decodes
+/// read an immutable snapshot of the loaded keys, so only those missing a key wait on a refresh.
+pub struct JwtDecoder {
+    settings: DecoderSettings,
+    state: ArcSwap<State>,
+    // Serializes refreshes so that callers waiting on one reuse its results.
+    loads: Mutex<Loads>,
+    load: Load,
+}
+
+/// Loader threads, at most one per URL. A load outliving the refresh that started it is not
+/// started again, and its result is used by a later refresh once it completes.
+struct Loads {
+    /// When each load still running was started.
+    in_flight: HashMap<Url, Instant>,
+    results_tx: mpsc::Sender<Loaded>,
+    results_rx: mpsc::Receiver<Loaded>,
+}
+
+impl Default for Loads {
+    fn default() -> Self {
+        let (results_tx, results_rx) = mpsc::channel();
+        Self {
+            in_flight: HashMap::new(),
+            results_tx,
+            results_rx,
+        }
+    }
+}
+
+impl JwtDecoder {
+    pub fn new(config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
+        Self::with_load(config, Arc::new(keys::load_jwks))
+    }
+
+    fn with_load(config: config::JwtDecoder, load: Load) -> Result<Self, JwtDecoderError> {
+        #[allow(unreachable_patterns)]
+        match &config.kind {
+            config::JwtDecoderKind::Blocking(_) => {}
+            other_kind => {
+                return Err(JwtDecoderError::new_failed_precondition(format!(
+                    "Unsupported JwtDecoder kind `{}` for blocking JwtDecoder",
+                    other_kind.as_ref()
+                )))
+            }
+        }
+
+        Ok(Self {
+            settings: DecoderSettings::new(config)?,
+            state: Default::default(),
+            loads: Default::default(),
+            load,
+        })
+    }
+
+    /// Lists the keys currently loaded from the JWKS URLs, refreshing any expired JWKS first.
+    pub fn keys(&self) -> Result<Vec<JwkInfo>, JwtDecoderError> {
+        self.refresh_all()?;
+        Ok(self.state.load().keys.clone())
+    }
+
+    /// Reports the state of each JWKS URL as of its last fetch, without refreshing.
+    pub fn status(&self) -> Vec<JwksSourceStatus> {
+        let now = (Instant::now(), SystemTime::now());
+        keys::status(
+            &self.settings,
+            &self.state.load().url_to_jwks,
+            |expiration| keys::system_time(expiration, now.0, now.1),
+        )
+    }
+
+    fn jwk(&self, kid: &str) -> Result<Option<Arc<VerificationKey>>, JwtDecoderError> {
+        // Immediately return an unexpired JWK if available.
+        if let Some(jwk) = self.state.load().jwk(kid, Instant::now()) {
+            metrics::record_key_cache(true);
+            return Ok(Some(jwk));
+        }
+        metrics::record_key_cache(false);
+
+        // Otherwise, refresh all expired.
+        self.refresh_all()?;
+
+        // Then, just return whatever is found.
+        let state = self.state.load();
+        if let Some(jwk) = state.jwk(kid, Instant::now()) {
+            return Ok(Some(jwk));
+        }
+        match keys::jwks_unavailable(&self.settings, &state.url_to_jwks) {
+            Some(err) => Err(err),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetches all URLs in parallel, giving up on those not finished within `max_wait`. Also
+    /// returns results of loads started by earlier refreshes that completed meanwhile.
+    fn fetch_all(&self, loads: &mut Loads, urls: Vec<Url>) -> Vec<(Url, JwksResult)> {
+        let start = Instant::now();
+        let deadline = start + self.settings.max_wait;
+        for url in &urls {
+            if loads.in_flight.contains_key(url) {
+                continue;
+            }
+            loads.in_flight.insert(url.clone(), start);
+            let url = url.clone();
+            let load = self.load.clone();
+            let limits = self.settings.limits.clone();
+            let results_tx = loads.results_tx.clone();
+            thread::spawn(move || {
+                let _span = info_span!("fetch_jwks", url = url.as_str()).entered();
+                let result = load(&url, &limits);
+                let _ = results_tx.send((url, result));
+            });
+        }
+
+        let mut results = HashMap::new();
+        while urls.iter().any(|url| !results.contains_key(url)) {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match loads.results_rx.recv_timeout(wait) {
+                Ok((url, result)) => {
+                    let started = loads.in_flight.remove(&url).unwrap_or(start);
+                    metrics::record_jwks_fetch(&url, started.elapsed(), &result);
+                    results.insert(url, result);
+                }
+                Err(_) => break,
+            }
+        }
+
+        for url in urls {
+            results.entry(url).or_insert_with_key(|url| {
+                let result = Err(JwtDecoderError::new_jwks_unavailable(
+                    "timed out".to_owned(),
+                ));
+                metrics::record_jwks_fetch(url, start.elapsed(), &result);
+                result
+            });
+        }
+
+        let expiration = Instant::now() + self.settings.ttl;
+        results
+            .into_iter()
+            .map(|(url, jwks)| {
+                let result = JwksResult::new(&self.settings, &url, jwks, expiration);
+                (url, result)
+            })
+            .collect()
+    }
+
+    fn refresh_all(&self) -> Result<(), JwtDecoderError> {
+        let mut loads = lock(&self.loads)?;
+        let state = self.state.load();
+        let now = Instant::now();
+
+        // Checked after acquiring the lock so that a refresh just completed is not repeated.
+        let expired = self
+            .settings
+            .jwks_urls
+            .iter()
+            .filter(|jwks_url| state.is_expired(jwks_url, now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let mut url_to_jwks = state.url_to_jwks.clone();
+        url_to_jwks.extend(self.fetch_all(&mut loads, expired));
+
+        let state = State::new(&self.settings, url_to_jwks);
+        metrics::record_keys_loaded(&self.settings.jwks_urls, &state.keys);
+        self.state.store(Arc::new(state));
+
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>, JwtDecoderError> {
+    mutex
+        .lock()
+        .map_err(|err| JwtDecoderError::new_internal_error(err.to_string()))
+}
+
+impl JwtDecodeBlocking for JwtDecoder {
+    fn decode(&self, token: &str) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
+        let result = (|| {
+            let kid = keys::token_kid(token)?;
+            let key = self
+                .jwk(&kid)?
+                .ok_or_else(|| JwtDecoderError::new_unknown_kid(kid.to_owned()))?;
+            keys::validate_token(token, &key, &self.settings.validation)
+        })();
+        metrics::record_decode(TokenFormat::Jwt, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc, Mutex,
+        },
+        thread::sleep,
+        time::Duration,
+    };
+
+    use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{
+        encode,
+        jwk::{
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet,
+        },
+        Algorithm, EncodingKey, Header,
+    };
+    use rcgen::KeyPair;
+    use serde_json::json;
+    use tracing_test::traced_test;
+    use url::Url;
+
+    use crate::{
+        blocking::JwtDecoder, config, error::JwtDecoderError, keys::Limits, tokio,
+        JwtDecodeBlocking,
+    };
+
+    fn jwk(kid: &str, key: &KeyPair) -> Jwk {
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let point = key.public_key_raw();
+        Jwk {
+            common: CommonParameters {
+                key_id: Some(kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }),
+        }
+    }
+
+    fn token(kid: &str, key: &KeyPair) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid.to_owned());
+        encode(
+            &header,
+            &json!({ "sub": "user@example.com", "exp": u64::MAX / 2 }),
+            &EncodingKey::from_ec_der(&key.serialize_der()),
+        )
+        .unwrap()
+    }
+
+    fn config(jwks_url: Url, kind: config::JwtDecoderKind) -> config::JwtDecoder {
+        config::JwtDecoder {
+            jwks_urls: vec![jwks_url],
+            algorithms: vec![Algorithm::ES256],
+            required_spec_claims: vec!["sub".to_owned()],
+            valid_audiences: vec![],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: Some(Duration::from_secs(1)),
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn blocking_works() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("keys.jwks");
+        let jwks_url = Url::from_file_path(&temp_file).unwrap();
+
+        let old_key = KeyPair::generate().unwrap();
+        let new_key = KeyPair::generate().unwrap();
+        let write_jwks = |keys: Vec<Jwk>| {
+            std::fs::write(&temp_file, serde_json::to_vec(&JwkSet { keys }).unwrap()).unwrap()
+        };
+
+        assert!(tokio::JwtDecoder::new(config(
+            jwks_url.clone(),
+            config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {})
+        ))
+        .is_err());
+
+        let jwt_decoder = JwtDecoder::new(config(
+            jwks_url,
+            config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {}),
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            jwt_decoder.decode(&token("old", &old_key)),
+            Err(JwtDecoderError::JwksUnavailable { .. })
+        ));
+        sleep(Duration::from_millis(1100));
+
+        write_jwks(vec![jwk("old", &old_key)]);
+        let token_data = jwt_decoder.decode(&token("old", &old_key)).unwrap();
+        assert_eq!(
+            token_data.claims.get("sub").and_then(|v| v.as_str()),
+            Some("user@example.com")
+        );
+
+        // The rotated key is not seen until the cached JWKS expires.
+        write_jwks(vec![jwk("new", &new_key)]);
+        assert_eq!(
+            jwt_decoder.decode(&token("new", &new_key)).err(),
+            Some(JwtDecoderError::new_unknown_kid("new".to_owned()))
+        );
+        sleep(Duration::from_millis(1100));
+        assert!(jwt_decoder.decode(&token("new", &new_key)).is_ok());
+        assert!(jwt_decoder.decode(&token("old", &old_key)).is_err());
+    }
+
+    #[test]
+    fn loads_at_most_once_per_url() {
+        let key = KeyPair::generate().unwrap();
+        let jwks = JwkSet {
+            keys: vec![jwk("a", &key)],
+        };
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let jwt_decoder = JwtDecoder::with_load(
+            config::JwtDecoder {
+                jwks_max_wait: Some(Duration::from_millis(100)),
+                jwks_ttl: Some(Duration::from_millis(1)),
+                ..config(
+                    Url::parse("file:///hung.jwks").unwrap(),
+                    config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {}),
+                )
+            },
+            Arc::new({
+                let loads = loads.clone();
+                move |_: &Url, _: &Limits| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    let _ = release_rx.lock().unwrap().recv();
+                    Ok(jwks.clone())
+                }
+            }),
+        )
+        .unwrap();
+
+        // A hung load times out each refresh without another being started.
+        for _ in 0..3 {
+            assert!(jwt_decoder.keys().unwrap().is_empty());
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // Its result is used by the refresh after it completes.
+        release_tx.send(()).unwrap();
+        sleep(Duration::from_millis(10));
+        assert_eq!(jwt_decoder.keys().unwrap().len(), 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+
+    #[traced_test]
+    #[test]
+    fn screens_each_fetch_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("keys.jwks");
+        let key = KeyPair::generate().unwrap();
+        let keys = vec![
+            jwk("good", &key),
+            Jwk {
+                common: CommonParameters::default(),
+                ..jwk("", &key)
+            },
+        ];
+        std::fs::write(&temp_file, serde_json::to_vec(&JwkSet { keys }).unwrap()).unwrap();
+
+        let jwt_decoder = JwtDecoder::new(config(
+            Url::from_file_path(&temp_file).unwrap(),
+            config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {}),
+        ))
+        .unwrap();
+        for _ in 0..3 {
+            assert_eq!(jwt_decoder.keys().unwrap().len(), 1);
+            assert_eq!(jwt_decoder.status()[0].rejected.len(), 1);
+        }
+
+        logs_assert(|lines| {
+            match lines
+                .iter()
+                .filter(|line| line.contains("Rejected JWK"))
+                .count()
+            {
+                1 => Ok(()),
+                count => Err(format!("Rejected JWK logged {count} times")),
+            }
+        });
+    }
+}
@@ -1,17 +1,25 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
 use jsonwebtoken::Algorithm;
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
 use serde_with::{serde_as, DurationSecondsWithFrac};
 use url::Url;
 
+use crate::x5c::TrustAnchors;
+
 #[serde_as]
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct JwtDecoder {
+    /// `file:` or base64 encoded `data:` JWKS URLs in order of descending priority.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub jwks_urls: Vec<Url>,
 
+    /// Algorithms a token's `alg` header must be one of, defaulting to RS256. Keys of different
+    /// types may share a JWKS; each token must also match its key's type and, if the JWK
+    /// declares one, its `alg`.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<AlgorithmName>")]
     pub algorithms: Vec<Algorithm>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -25,23 +33,341 @@ pub struct JwtDecoder {
 
     #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
     #[serde(rename = "jwks_max_wait_sec")]
+    #[schemars(with = "Option<f64>")]
     pub jwks_max_wait: Option<Duration>,
 
     #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
     #[serde(rename = "jwks_ttl_sec")]
+    #[schemars(with = "Option<f64>")]
     pub jwks_ttl: Option<Duration>,
 
+    /// PEM bundle of trust anchors. When set, only JWKs with an `x5c` chain that validates
+    /// against one of these certificates are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x5c_trust_anchors_pem: Option<String>,
+
+    #[serde(default)]
+    pub jwks_limits: JwksLimits,
+
     #[serde(flatten)]
     pub kind: JwtDecoderKind,
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, strum::AsRefStr)]
+/// Limits applied to each JWKS source. A document that is too large or has too many keys is
+/// rejected as a whole, while keys that are too small are rejected individually.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct JwksLimits {
+    /// Defaults to 1 MiB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_document_bytes: Option<usize>,
+
+    /// Defaults to 100.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_keys: Option<usize>,
+
+    /// Defaults to 2048.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rsa_bits: Option<usize>,
+
+    /// Minimum `oct` key length for HMAC, defaulting to 32.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_hmac_bytes: Option<usize>,
+}
+
+#[derive(
+    Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, strum::AsRefStr, JsonSchema,
+)]
 #[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 #[non_exhaustive]
 pub enum JwtDecoderKind {
+    Blocking(BlockingJwtDecoder),
     Tokio(TokioJwtDecoder),
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BlockingJwtDecoder {}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct TokioJwtDecoder {}
+
+/// The names of [Algorithm] as they appear in config.
+#[derive(JsonSchema)]
+#[schemars(rename = "Algorithm")]
+#[allow(dead_code, clippy::upper_case_acronyms)]
+enum AlgorithmName {
+    HS256,
+    HS384,
+    HS512,
+    ES256,
+    ES384,
+    RS256,
+    RS384,
+    RS512,
+    PS256,
+    PS384,
+    PS512,
+    EdDSA,
+}
+
+/// A problem found by [JwtDecoder::validate] in the field at `path`.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+#[error("{path}: {message}")]
+pub struct ConfigProblem {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Claims that [JwtDecoder::required_spec_claims] may require.
+const SPEC_CLAIMS: [&str; 5] = ["exp", "nbf", "aud", "iss", "sub"];
+
+impl JwtDecoder {
+    /// The JSON Schema of the config as accepted by serde.
+    pub fn json_schema() -> RootSchema {
+        schema_for!(JwtDecoder)
+    }
+
+    /// Checks the config for problems that would otherwise only surface when decoding, reporting
+    /// all of them at once. Decoders refuse configs that fail validation.
+    pub fn validate(&self) -> Result<(), Vec<ConfigProblem>> {
+        let mut problems = Vec::new();
+
+        if self.jwks_urls.is_empty() {
+            problems.push(ConfigProblem::new(
+                "jwks_urls",
+                "At least one JWKS URL is required",
+            ));
+        }
+        let mut jwks_urls = HashSet::new();
+        for (i, url) in self.jwks_urls.iter().enumerate() {
+            let path = format!("jwks_urls[{i}]");
+            match url.scheme() {
+                "file" if url.to_file_path().is_err() => {
+                    problems.push(ConfigProblem::new(path, "Not a valid file path"))
+                }
+                "file" => {}
+                "data" if !url.path().contains(";base64,") => {
+                    problems.push(ConfigProblem::new(path, "Data URL must be base64 encoded"))
+                }
+                "data" => {}
+                scheme => problems.push(ConfigProblem::new(
+                    path,
+                    format!("Unsupported scheme `{scheme}`"),
+                )),
+            }
+            if !jwks_urls.insert(url) {
+                problems.push(ConfigProblem::new(
+                    format!("jwks_urls[{i}]"),
+                    "Duplicate JWKS URL",
+                ));
+            }
+        }
+
+        for (i, claim) in self.required_spec_claims.iter().enumerate() {
+            if !SPEC_CLAIMS.contains(&claim.as_str()) {
+                problems.push(ConfigProblem::new(
+                    format!("required_spec_claims[{i}]"),
+                    format!("`{claim}` is not one of {}", SPEC_CLAIMS.join(", ")),
+                ));
+            }
+        }
+        for (field, values) in [
+            ("valid_audiences", &self.valid_audiences),
+            ("valid_issuers", &self.valid_issuers),
+        ] {
+            for (i, value) in values.iter().enumerate() {
+                if value.is_empty() {
+                    problems.push(ConfigProblem::new(format!("{field}[{i}]"), "Empty value"));
+                }
+            }
+        }
+
+        for (field, duration) in [
+            ("jwks_max_wait_sec", self.jwks_max_wait),
+            ("jwks_ttl_sec", self.jwks_ttl),
+        ] {
+            if duration == Some(Duration::ZERO) {
+                problems.push(ConfigProblem::new(field, "Must be greater than zero"));
+            }
+        }
+
+        if let Some(pem) = &self.x5c_trust_anchors_pem {
+            if let Err(err) = TrustAnchors::from_pem(pem) {
+                problems.push(ConfigProblem::new("x5c_trust_anchors_pem", err.to_string()));
+            }
+            // HMAC keys have no certificate chain to validate.
+            if self.algorithms.iter().any(|algorithm| {
+                matches!(
+                    algorithm,
+                    Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+                )
+            }) {
+                problems.push(ConfigProblem::new(
+                    "algorithms",
+                    "HMAC algorithms cannot be used with x5c trust anchors",
+                ));
+            }
+        }
+
+        for (field, limit) in [
+            ("max_document_bytes", self.jwks_limits.max_document_bytes),
+            ("max_keys", self.jwks_limits.max_keys),
+        ] {
+            if limit == Some(0) {
+                problems.push(ConfigProblem::new(
+                    format!("jwks_limits.{field}"),
+                    "Must be greater than zero",
+                ));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems),
+        }
+    }
+}
+
+/// How a [crate::authorization::Principal] is built from token claims.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Principal {
+    /// Dot-separated claim paths holding a role or array of roles, e.g. `roles` or
+    /// `realm_access.roles`, or JSON pointers for namespaced claims, e.g.
+    /// `/https:~1~1example.com~1roles`. A `*` segment matches any key and scopes the roles below it
+    /// to that key as a resource, e.g. `resource_access.*.roles`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub role_claims: Vec<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use jsonwebtoken::Algorithm;
+    use url::Url;
+
+    use super::{ConfigProblem, JwksLimits, JwtDecoder, JwtDecoderKind, TokioJwtDecoder};
+
+    fn config() -> JwtDecoder {
+        JwtDecoder {
+            jwks_urls: vec![
+                Url::parse("file:///etc/jwks/primary.jwks").unwrap(),
+                Url::parse("data:application/json;base64,eyJrZXlzIjpbXX0=").unwrap(),
+            ],
+            algorithms: vec![Algorithm::RS256, Algorithm::EdDSA],
+            required_spec_claims: vec!["exp".to_owned(), "aud".to_owned()],
+            valid_audiences: vec!["some-users".to_owned()],
+            valid_issuers: vec!["an-issuer".to_owned()],
+            jwks_max_wait: Some(Duration::from_millis(1500)),
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: JwksLimits {
+                max_keys: Some(10),
+                ..Default::default()
+            },
+            kind: JwtDecoderKind::Tokio(TokioJwtDecoder {}),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let config = config();
+        assert_eq!(config.validate(), Ok(()));
+
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(serde_yaml::from_str::<JwtDecoder>(&yaml).unwrap(), config);
+
+        let toml = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<JwtDecoder>(&toml).unwrap(), config);
+
+        let config = toml::from_str::<JwtDecoder>(
+            r#"
+jwks_urls = ["file:///etc/jwks/primary.jwks"]
+algorithms = ["ES256"]
+jwks_ttl_sec = 300
+
+[jwks_limits]
+min_rsa_bits = 3072
+
+[tokio]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.jwks_ttl, Some(Duration::from_secs(300)));
+        assert_eq!(config.jwks_limits.min_rsa_bits, Some(3072));
+        assert_eq!(config.kind, JwtDecoderKind::Tokio(TokioJwtDecoder {}));
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let mut config = config();
+        config
+            .jwks_urls
+            .push(Url::parse("https://example.com/jwks").unwrap());
+        config.jwks_urls.push(config.jwks_urls[0].clone());
+        config.algorithms.push(Algorithm::HS256);
+        config.required_spec_claims.push("roles".to_owned());
+        config.valid_issuers.push(String::new());
+        config.jwks_ttl = Some(Duration::ZERO);
+        config.x5c_trust_anchors_pem = Some("not a certificate".to_owned());
+        config.jwks_limits.max_document_bytes = Some(0);
+
+        let problems = config.validate().unwrap_err();
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| problem.path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "jwks_urls[2]",
+                "jwks_urls[3]",
+                "required_spec_claims[2]",
+                "valid_issuers[1]",
+                "jwks_ttl_sec",
+                "x5c_trust_anchors_pem",
+                "algorithms",
+                "jwks_limits.max_document_bytes",
+            ]
+        );
+        assert_eq!(
+            problems[0],
+            ConfigProblem::new("jwks_urls[2]", "Unsupported scheme `https`")
+        );
+
+        config.jwks_urls.clear();
+        assert!(config.validate().unwrap_err().contains(&ConfigProblem::new(
+            "jwks_urls",
+            "At least one JWKS URL is required"
+        )));
+    }
+
+    #[test]
+    fn json_schema() {
+        let schema = serde_json::to_value(JwtDecoder::json_schema()).unwrap();
+        let properties = &schema["properties"];
+        for property in ["jwks_urls", "algorithms", "jwks_ttl_sec", "jwks_limits"] {
+            assert!(properties.get(property).is_some(), "{property}");
+        }
+        assert!(properties.get("jwks_ttl").is_none());
+        assert_eq!(
+            schema["definitions"]["Algorithm"]["enum"]
+                .as_array()
+                .map(Vec::len),
+            Some(12)
+        );
+    }
+}
@@ -1,21 +1,278 @@
 use derive_new::new;
+use jsonwebtoken::errors::ErrorKind;
 
-#[derive(Clone, new, thiserror::Error, Debug)]
+#[derive(Clone, new, thiserror::Error, Debug, strum_macros::EnumDiscriminants, Eq, PartialEq)]
+#[strum_discriminants(derive(strum::AsRefStr))]
+#[strum_discriminants(name(JwtDecoderErrorReason))]
+#[strum_discriminants(strum(serialize_all = "SCREAMING_SNAKE_CASE"))]
 pub enum JwtDecoderError {
+    #[error("JWT audience is not valid")]
+    BadAudience,
+    #[error("JWT issuer is not valid")]
+    BadIssuer,
+    #[error("JWT subject is not valid")]
+    BadSubject,
+    #[error("Certificate binding mismatch: {message}")]
+    CertificateBindingMismatch { message: String },
     #[error("JWT header parsing failed: {message}")]
     HeaderParsingFailed { message: String },
     #[error("Internal error: {message}")]
     InternalError { message: String },
-    #[error("JWKS fetch error: {message}")]
-    JwksFetchError { message: String },
+    #[error("JWT algorithm is not allowed")]
+    InvalidAlgorithm,
+    #[error("JWT signature is not valid")]
+    InvalidSignature,
+    #[error("JWKS unavailable: {message}")]
+    JwksUnavailable { message: String },
     #[error("Failed precondition: {message}")]
     FailedPrecondition { message: String },
+    #[error("Malformed JWT: {message}")]
+    MalformedToken { message: String },
     #[error("JWT was missing key ID `kid` option")]
     MissingKeyId,
+    #[error("JWT was missing required claim `{claim}`")]
+    MissingRequiredClaim { claim: String },
+    #[error("Permission denied: {message}")]
+    PermissionDenied { message: String },
     #[error("Service unavailable: {message}")]
     ServiceUnavailable { message: String },
+    #[error("JWT has expired")]
+    TokenExpired,
+    #[error("JWT is not valid yet")]
+    TokenNotYetValid,
+    #[error("No JWK found for kid `{kid}`")]
+    UnknownKid { kid: String },
+    #[error("Untrusted JWK for kid `{kid}`: {message}")]
+    UntrustedJwk { kid: String, message: String },
     #[error("Unsupported JWK for kid `{kid}`: {message}")]
     UnsupportedJwk { kid: String, message: String },
     #[error("Validation failed: {message}")]
     ValidationFailed { message: String },
 }
+
+impl JwtDecoderError {
+    pub const DOMAIN: &'static str = "com.appbiotic.auth.jwt-decoder";
+
+    /// Error details that clients may use alongside the reason, keyed by field name.
+    pub fn metadata(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::MissingRequiredClaim { claim } => vec![("claim", claim.to_owned())],
+            Self::UnknownKid { kid }
+            | Self::UntrustedJwk { kid, message: _ }
+            | Self::UnsupportedJwk { kid, message: _ } => vec![("kid", kid.to_owned())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for JwtDecoderError {
+    fn from(value: jsonwebtoken::errors::Error) -> Self {
+        match value.kind() {
+            ErrorKind::ExpiredSignature => Self::new_token_expired(),
+            ErrorKind::ImmatureSignature => Self::new_token_not_yet_valid(),
+            ErrorKind::InvalidAudience => Self::new_bad_audience(),
+            ErrorKind::InvalidIssuer => Self::new_bad_issuer(),
+            ErrorKind::InvalidSubject => Self::new_bad_subject(),
+            ErrorKind::InvalidAlgorithm | ErrorKind::MissingAlgorithm => {
+                Self::new_invalid_algorithm()
+            }
+            ErrorKind::InvalidSignature => Self::new_invalid_signature(),
+            ErrorKind::MissingRequiredClaim(claim) => {
+                Self::new_missing_required_claim(claim.to_owned())
+            }
+            ErrorKind::InvalidToken
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => Self::new_malformed_token(value.to_string()),
+            _ => Self::new_validation_failed(value.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct JwtDecoderErrorReports {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<JwtDecoderErrorReport>,
+}
+
+impl From<JwtDecoderErrorReport> for JwtDecoderErrorReports {
+    fn from(value: JwtDecoderErrorReport) -> Self {
+        Self {
+            errors: vec![value],
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct JwtDecoderErrorReport {
+    pub code: String,
+    pub message: String,
+}
+
+impl From<JwtDecoderError> for JwtDecoderErrorReport {
+    fn from(value: JwtDecoderError) -> Self {
+        Self {
+            code: JwtDecoderErrorReason::from(&value).as_ref().to_owned(),
+            message: value.to_string(),
+        }
+    }
+}
+
+/// Serializes as a [JwtDecoderErrorReport].
+impl serde::Serialize for JwtDecoderError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        JwtDecoderErrorReport::from(self.to_owned()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "google-rpc")]
+mod google_rpc {
+    use appbiotic_api_google_rpc::prost_serde::google::rpc::{Code, ErrorInfo, Status};
+    use prost::Message;
+
+    use super::{JwtDecoderError, JwtDecoderErrorReason};
+
+    const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+    impl JwtDecoderErrorReason {
+        pub fn code(&self) -> Code {
+            // Every reason is listed so that adding one requires choosing its code.
+            match self {
+                Self::InternalError => Code::Internal,
+                Self::FailedPrecondition | Self::UntrustedJwk | Self::UnsupportedJwk => {
+                    Code::FailedPrecondition
+                }
+                Self::JwksUnavailable | Self::ServiceUnavailable => Code::Unavailable,
+                Self::PermissionDenied => Code::PermissionDenied,
+                Self::BadAudience
+                | Self::BadIssuer
+                | Self::BadSubject
+                | Self::CertificateBindingMismatch
+                | Self::HeaderParsingFailed
+                | Self::InvalidAlgorithm
+                | Self::InvalidSignature
+                | Self::MalformedToken
+                | Self::MissingKeyId
+                | Self::MissingRequiredClaim
+                | Self::TokenExpired
+                | Self::TokenNotYetValid
+                | Self::UnknownKid
+                | Self::ValidationFailed => Code::Unauthenticated,
+            }
+        }
+    }
+
+    impl From<&JwtDecoderError> for ErrorInfo {
+        fn from(value: &JwtDecoderError) -> Self {
+            Self {
+                reason: JwtDecoderErrorReason::from(value).as_ref().to_owned(),
+                domain: JwtDecoderError::DOMAIN.to_owned(),
+                metadata: value
+                    .metadata()
+                    .into_iter()
+                    .map(|(key, value)| (key.to_owned(), value))
+                    .collect(),
+            }
+        }
+    }
+
+    impl From<&JwtDecoderError> for Status {
+        fn from(value: &JwtDecoderError) -> Self {
+            Self {
+                code: JwtDecoderErrorReason::from(value).code() as i32,
+                message: value.to_string(),
+                details: vec![prost_wkt_types::Any {
+                    type_url: ERROR_INFO_TYPE_URL.to_owned(),
+                    value: ErrorInfo::from(value).encode_to_vec(),
+                }],
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use appbiotic_api_google_rpc::prost_serde::google::rpc::{Code, ErrorInfo, Status};
+        use prost::Message;
+
+        use crate::error::JwtDecoderError;
+
+        #[test]
+        fn status_carries_error_info() {
+            let error = JwtDecoderError::new_unknown_kid("my-key".to_owned());
+            let status = Status::from(&error);
+
+            assert_eq!(status.code, Code::Unauthenticated as i32);
+            assert_eq!(status.details.len(), 1);
+            assert_eq!(
+                status.details[0].type_url,
+                "type.googleapis.com/google.rpc.ErrorInfo"
+            );
+
+            let error_info = ErrorInfo::decode(status.details[0].value.as_slice()).unwrap();
+            assert_eq!(error_info.reason, "UNKNOWN_KID");
+            assert_eq!(error_info.domain, JwtDecoderError::DOMAIN);
+            assert_eq!(
+                error_info.metadata.get("kid").map(String::as_str),
+                Some("my-key")
+            );
+        }
+
+        #[test]
+        fn reasons_map_to_codes_and_metadata() {
+            for (error, code, reason, metadata) in [
+                (
+                    JwtDecoderError::new_missing_required_claim("exp".to_owned()),
+                    Code::Unauthenticated,
+                    "MISSING_REQUIRED_CLAIM",
+                    vec![("claim", "exp")],
+                ),
+                (
+                    JwtDecoderError::new_untrusted_jwk("k1".to_owned(), "expired".to_owned()),
+                    Code::FailedPrecondition,
+                    "UNTRUSTED_JWK",
+                    vec![("kid", "k1")],
+                ),
+                (
+                    JwtDecoderError::new_jwks_unavailable("timeout".to_owned()),
+                    Code::Unavailable,
+                    "JWKS_UNAVAILABLE",
+                    vec![],
+                ),
+                (
+                    JwtDecoderError::new_permission_denied("scope".to_owned()),
+                    Code::PermissionDenied,
+                    "PERMISSION_DENIED",
+                    vec![],
+                ),
+                (
+                    JwtDecoderError::new_internal_error("bug".to_owned()),
+                    Code::Internal,
+                    "INTERNAL_ERROR",
+                    vec![],
+                ),
+                (
+                    JwtDecoderError::new_token_expired(),
+                    Code::Unauthenticated,
+                    "TOKEN_EXPIRED",
+                    vec![],
+                ),
+            ] {
+                let status = Status::from(&error);
+                assert_eq!(status.code, code as i32, "{error}");
+                assert_eq!(status.message, error.to_string());
+
+                let error_info = ErrorInfo::decode(status.details[0].value.as_slice()).unwrap();
+                assert_eq!(error_info.reason, reason);
+                assert_eq!(
+                    error_info.metadata,
+                    metadata
+                        .into_iter()
+                        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                        .collect()
+                );
+            }
+        }
+    }
+}
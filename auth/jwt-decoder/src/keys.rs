@@ -0,0 +1,779 @@
+//! JWKS loading, key selection and token validation shared by the [crate::tokio] and
+//! [crate::blocking] decoders, which differ only in how fetches are scheduled and awaited.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use base64::engine::{
+    general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse},
+    Algorithm, DecodingKey, TokenData, Validation,
+};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use tracing::warn;
+use url::Url;
+
+use crate::{config, error::JwtDecoderError, metrics, x5c::TrustAnchors};
+
+pub(crate) struct DecoderSettings {
+    pub jwks_urls: Vec<Url>,
+    pub max_wait: Duration,
+    pub ttl: Duration,
+    pub validation: Validation,
+    pub x5c_trust_anchors: Option<TrustAnchors>,
+    pub limits: Limits,
+}
+
+#[derive(Clone)]
+pub(crate) struct Limits {
+    pub max_document_bytes: usize,
+    pub max_keys: usize,
+    pub min_rsa_bits: usize,
+    pub min_hmac_bytes: usize,
+}
+
+impl Limits {
+    fn new(config: &config::JwksLimits) -> Self {
+        Self {
+            max_document_bytes: config.max_document_bytes.unwrap_or(1024 * 1024),
+            max_keys: config.max_keys.unwrap_or(100),
+            min_rsa_bits: config.min_rsa_bits.unwrap_or(2048),
+            min_hmac_bytes: config.min_hmac_bytes.unwrap_or(32),
+        }
+    }
+}
+
+impl DecoderSettings {
+    pub fn new(config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
+        config.validate().map_err(|problems| {
+            let problems = problems.iter().map(ToString::to_string).collect::<Vec<_>>();
+            JwtDecoderError::new_failed_precondition(format!(
+                "Invalid JwtDecoder config: {}",
+                problems.join("; ")
+            ))
+        })?;
+
+        let validation = {
+            // NOTE: algorithm in `new` will be overwritten.
+            let mut validation = Validation::new(Algorithm::RS256);
+            if !config.algorithms.is_empty() {
+                validation.algorithms = config.algorithms;
+            }
+            if !config.required_spec_claims.is_empty() {
+                validation.set_required_spec_claims(&config.required_spec_claims);
+            }
+            if !config.valid_audiences.is_empty() {
+                validation.set_audience(&config.valid_audiences);
+            }
+            if !config.valid_issuers.is_empty() {
+                validation.set_issuer(&config.valid_issuers);
+            }
+            validation
+        };
+
+        let x5c_trust_anchors = config
+            .x5c_trust_anchors_pem
+            .as_deref()
+            .map(TrustAnchors::from_pem)
+            .transpose()?;
+
+        Ok(Self {
+            jwks_urls: config.jwks_urls,
+            max_wait: config.jwks_max_wait.unwrap_or(Duration::from_secs(1)),
+            ttl: config.jwks_ttl.unwrap_or(Duration::from_secs(60)),
+            validation,
+            x5c_trust_anchors,
+            limits: Limits::new(&config.jwks_limits),
+        })
+    }
+}
+
+/// A key loaded from one of the JWKS URLs.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct JwkInfo {
+    pub kid: String,
+    pub key_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<KeyAlgorithm>,
+    pub source: Url,
+}
+
+/// A decoding key with its JWK `kty` and the algorithm its JWK is restricted to, if any.
+pub(crate) struct VerificationKey {
+    pub decoding_key: DecodingKey,
+    pub key_type: String,
+    pub algorithm: Option<KeyAlgorithm>,
+}
+
+/// The state of one JWKS URL as of its last fetch.
+#[serde_as]
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct JwksSourceStatus {
+    pub url: Url,
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<f64>>")]
+    pub last_fetch: Option<SystemTime>,
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<f64>>")]
+    pub expiration: Option<SystemTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JwtDecoderError>,
+    /// Kids of keys used for verification.
+    pub contributed_kids: Vec<String>,
+    /// Accepted keys not used because a higher priority URL has the same kid.
+    pub shadowed: Vec<ShadowedJwk>,
+    pub rejected: Vec<RejectedJwk>,
+}
+
+impl JwksSourceStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.last_fetch.is_some() && self.error.is_none() && self.rejected.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ShadowedJwk {
+    pub kid: String,
+    pub by: Url,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RejectedJwk {
+    pub kid: Option<String>,
+    pub error: JwtDecoderError,
+}
+
+#[derive(Clone)]
+pub(crate) struct JwksResult<I> {
+    pub jwks: Result<Arc<ScreenedJwks>, JwtDecoderError>,
+    pub fetched_at: SystemTime,
+    pub expiration: I,
+}
+
+impl<I> JwksResult<I> {
+    pub fn new(
+        settings: &DecoderSettings,
+        url: &Url,
+        jwks: Result<JwkSet, JwtDecoderError>,
+        expiration: I,
+    ) -> Self {
+        Self {
+            jwks: jwks.map(|jwks| Arc::new(ScreenedJwks::new(settings, url, jwks))),
+            fetched_at: SystemTime::now(),
+            expiration,
+        }
+    }
+}
+
+/// A fetched JWKS split into keys usable for verification and those rejected by the settings,
+/// screened once when its fetch completes.
+pub(crate) struct ScreenedJwks {
+    pub jwks: JwkSet,
+    pub accepted: Vec<(String, Arc<VerificationKey>)>,
+    pub rejected: Vec<RejectedJwk>,
+}
+
+impl ScreenedJwks {
+    pub fn new(settings: &DecoderSettings, url: &Url, jwks: JwkSet) -> Self {
+        let (accepted, rejected) = screen(settings, &jwks);
+        for rejected in &rejected {
+            warn!(
+                url = metrics::url_label(url),
+                kid = rejected.kid,
+                error = %rejected.error,
+                "Rejected JWK"
+            );
+        }
+        let accepted = accepted
+            .into_iter()
+            .map(|(key, kid, decoding_key)| {
+                let key = VerificationKey {
+                    decoding_key,
+                    key_type: key_type(&key.algorithm).to_owned(),
+                    algorithm: key.common.key_algorithm,
+                };
+                (kid.to_owned(), Arc::new(key))
+            })
+            .collect();
+        Self {
+            jwks,
+            accepted,
+            rejected,
+        }
+    }
+}
+
+/// Loaded JWKS and the keys selected from them, replaced as a whole whenever a JWKS is fetched.
+pub(crate) struct KeyState<I> {
+    pub url_to_jwks: HashMap<Url, JwksResult<I>>,
+    /// Keys used for verification in order of URLs with descending priority.
+    pub keys: Vec<JwkInfo>,
+    kid_to_jwk: HashMap<String, KeyEntry<I>>,
+}
+
+struct KeyEntry<I> {
+    jwk: Arc<VerificationKey>,
+    expiration: I,
+}
+
+impl<I> Default for KeyState<I> {
+    fn default() -> Self {
+        Self {
+            url_to_jwks: HashMap::new(),
+            keys: Vec::new(),
+            kid_to_jwk: HashMap::new(),
+        }
+    }
+}
+
+impl<I: Copy + Ord> KeyState<I> {
+    /// Merges the screened keys of each JWKS, without side effects so that it may be retried.
+    pub fn new(settings: &DecoderSettings, url_to_jwks: HashMap<Url, JwksResult<I>>) -> Self {
+        let mut keys = Vec::new();
+        let mut kid_to_jwk = HashMap::new();
+        for url in &settings.jwks_urls {
+            let Some(result) = url_to_jwks.get(url) else {
+                continue;
+            };
+            let Ok(jwks) = &result.jwks else {
+                continue;
+            };
+            for (kid, jwk) in &jwks.accepted {
+                // Do not overwrite JWK with lower priority URL JWK
+                if kid_to_jwk.contains_key(kid) {
+                    continue;
+                }
+                kid_to_jwk.insert(
+                    kid.to_owned(),
+                    KeyEntry {
+                        jwk: jwk.clone(),
+                        expiration: result.expiration,
+                    },
+                );
+                keys.push(JwkInfo {
+                    kid: kid.to_owned(),
+                    key_type: jwk.key_type.to_owned(),
+                    algorithm: jwk.algorithm,
+                    source: url.to_owned(),
+                });
+            }
+        }
+        Self {
+            url_to_jwks,
+            keys,
+            kid_to_jwk,
+        }
+    }
+
+    pub fn jwk(&self, kid: &str, now: I) -> Option<Arc<VerificationKey>> {
+        self.kid_to_jwk
+            .get(kid)
+            .filter(|entry| entry.expiration >= now)
+            .map(|entry| entry.jwk.clone())
+    }
+
+    pub fn is_expired(&self, url: &Url, now: I) -> bool {
+        match self.url_to_jwks.get(url) {
+            Some(entry) => entry.expiration < now,
+            None => true,
+        }
+    }
+}
+
+/// Reads a JWKS from a `file:` or `data:` URL, blocking on file I/O.
+pub(crate) fn load_jwks(url: &Url, limits: &Limits) -> Result<JwkSet, JwtDecoderError> {
+    match url.scheme() {
+        "data" => parse_data_url(url, limits),
+        _ => read_jwks_file(&jwks_file_path(url)?, limits),
+    }
+}
+
+fn read_jwks_file(path: &Path, limits: &Limits) -> Result<JwkSet, JwtDecoderError> {
+    let file =
+        File::open(path).map_err(|err| JwtDecoderError::new_jwks_unavailable(err.to_string()))?;
+    let metadata = file
+        .metadata()
+        .map_err(|err| JwtDecoderError::new_jwks_unavailable(err.to_string()))?;
+    check_document_size(metadata.len(), limits)?;
+    let mut data = Vec::with_capacity(metadata.len() as usize);
+    // Read one byte past the limit in case the file grew since its metadata was read.
+    file.take(limits.max_document_bytes as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|err| JwtDecoderError::new_jwks_unavailable(err.to_string()))?;
+    parse_jwks(&data, limits)
+}
+
+fn jwks_file_path(url: &Url) -> Result<PathBuf, JwtDecoderError> {
+    match url.scheme() {
+        "file" => url.to_file_path().map_err(|_| {
+            JwtDecoderError::new_jwks_unavailable("Failed to extract path from file URL".to_owned())
+        }),
+        scheme => Err(JwtDecoderError::new_jwks_unavailable(format!(
+            "Unsupported JWKS URL scheme `{scheme}`"
+        ))),
+    }
+}
+
+/// Decodes a JWKS embedded in a `data:application/json;base64,` URL.
+fn parse_data_url(url: &Url, limits: &Limits) -> Result<JwkSet, JwtDecoderError> {
+    let data = url
+        .path()
+        .split_once(',')
+        .and_then(|(media_type, data)| media_type.ends_with(";base64").then_some(data))
+        .ok_or_else(|| {
+            JwtDecoderError::new_jwks_unavailable(
+                "Only base64 encoded `data:` JWKS URLs are supported".to_owned(),
+            )
+        })?;
+    let data = STANDARD
+        .decode(data)
+        .map_err(|err| JwtDecoderError::new_jwks_unavailable(err.to_string()))?;
+    parse_jwks(&data, limits)
+}
+
+fn check_document_size(size: u64, limits: &Limits) -> Result<(), JwtDecoderError> {
+    if size > limits.max_document_bytes as u64 {
+        return Err(JwtDecoderError::new_jwks_unavailable(format!(
+            "JWKS document exceeds limit of {} bytes",
+            limits.max_document_bytes
+        )));
+    }
+    Ok(())
+}
+
+fn parse_jwks(data: &[u8], limits: &Limits) -> Result<JwkSet, JwtDecoderError> {
+    check_document_size(data.len() as u64, limits)?;
+    let jwks: JwkSet = serde_json::from_slice(data)
+        .map_err(|err| JwtDecoderError::new_jwks_unavailable(err.to_string()))?;
+    if jwks.keys.len() > limits.max_keys {
+        return Err(JwtDecoderError::new_jwks_unavailable(format!(
+            "JWKS has {} keys, exceeding limit of {}",
+            jwks.keys.len(),
+            limits.max_keys
+        )));
+    }
+    Ok(jwks)
+}
+
+/// Splits the JWKS into keys usable for verification and those rejected by the settings.
+fn screen<'a>(
+    settings: &DecoderSettings,
+    jwks: &'a JwkSet,
+) -> (Vec<(&'a Jwk, &'a str, DecodingKey)>, Vec<RejectedJwk>) {
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for key in &jwks.keys {
+        let Some(kid) = &key.common.key_id else {
+            rejected.push(RejectedJwk {
+                kid: None,
+                error: JwtDecoderError::new_unsupported_jwk(
+                    String::new(),
+                    "JWK has no `kid`".to_owned(),
+                ),
+            });
+            continue;
+        };
+        let result = screen_key(settings, kid, key);
+        match result {
+            Ok(decoding_key) => accepted.push((key, kid.as_str(), decoding_key)),
+            Err(error) => rejected.push(RejectedJwk {
+                kid: Some(kid.to_owned()),
+                error,
+            }),
+        }
+    }
+    (accepted, rejected)
+}
+
+fn screen_key(
+    settings: &DecoderSettings,
+    kid: &str,
+    key: &Jwk,
+) -> Result<DecodingKey, JwtDecoderError> {
+    let unsupported =
+        |message: String| JwtDecoderError::new_unsupported_jwk(kid.to_owned(), message);
+    if key.common.public_key_use == Some(PublicKeyUse::Encryption) {
+        return Err(unsupported("JWK is for encryption".to_owned()));
+    }
+    let decode = |value: &str| {
+        URL_SAFE_NO_PAD
+            .decode(value)
+            .map_err(|err| unsupported(format!("Invalid base64: {err}")))
+    };
+    match &key.algorithm {
+        AlgorithmParameters::RSA(params) => {
+            let bits = bit_length(&decode(&params.n)?);
+            if bits < settings.limits.min_rsa_bits {
+                return Err(unsupported(format!(
+                    "RSA key of {bits} bits is smaller than {} bits",
+                    settings.limits.min_rsa_bits
+                )));
+            }
+        }
+        AlgorithmParameters::OctetKey(params) => {
+            let bytes = decode(&params.value)?.len();
+            if bytes < settings.limits.min_hmac_bytes {
+                return Err(unsupported(format!(
+                    "HMAC key of {bytes} bytes is smaller than {} bytes",
+                    settings.limits.min_hmac_bytes
+                )));
+            }
+        }
+        AlgorithmParameters::EllipticCurve(_) | AlgorithmParameters::OctetKeyPair(_) => {}
+    }
+    if let Some(trust_anchors) = &settings.x5c_trust_anchors {
+        trust_anchors.validate(key)?;
+    }
+    DecodingKey::from_jwk(key).map_err(|err| unsupported(err.to_string()))
+}
+
+fn bit_length(bytes: &[u8]) -> usize {
+    match bytes.iter().position(|b| *b != 0) {
+        Some(start) => (bytes.len() - start) * 8 - bytes[start].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// Reports each configured URL in order of descending priority, converting expirations to
+/// system time with `expires_at`.
+pub(crate) fn status<I: Copy>(
+    settings: &DecoderSettings,
+    url_to_jwks: &HashMap<Url, JwksResult<I>>,
+    expires_at: impl Fn(I) -> SystemTime,
+) -> Vec<JwksSourceStatus> {
+    let mut kid_to_url = HashMap::<String, &Url>::new();
+    settings
+        .jwks_urls
+        .iter()
+        .map(|url| {
+            let mut status = JwksSourceStatus {
+                url: url.to_owned(),
+                last_fetch: None,
+                expiration: None,
+                error: None,
+                contributed_kids: Vec::new(),
+                shadowed: Vec::new(),
+                rejected: Vec::new(),
+            };
+            let Some(result) = url_to_jwks.get(url) else {
+                return status;
+            };
+            status.last_fetch = Some(result.fetched_at);
+            status.expiration = Some(expires_at(result.expiration));
+            match &result.jwks {
+                Ok(jwks) => {
+                    for (kid, _) in &jwks.accepted {
+                        match kid_to_url.get(kid) {
+                            Some(by) => status.shadowed.push(ShadowedJwk {
+                                kid: kid.to_owned(),
+                                by: (*by).to_owned(),
+                            }),
+                            None => {
+                                kid_to_url.insert(kid.to_owned(), url);
+                                status.contributed_kids.push(kid.to_owned());
+                            }
+                        }
+                    }
+                    status.rejected = jwks.rejected.clone();
+                }
+                Err(err) => status.error = Some(err.to_owned()),
+            }
+            status
+        })
+        .collect()
+}
+
+fn key_type(algorithm: &AlgorithmParameters) -> &'static str {
+    match algorithm {
+        AlgorithmParameters::EllipticCurve(_) => "EC",
+        AlgorithmParameters::RSA(_) => "RSA",
+        AlgorithmParameters::OctetKey(_) => "oct",
+        AlgorithmParameters::OctetKeyPair(_) => "OKP",
+    }
+}
+
+/// Converts an instant to system time relative to a pair of the current instant and system time.
+pub(crate) fn system_time(
+    instant: std::time::Instant,
+    now: std::time::Instant,
+    system_now: SystemTime,
+) -> SystemTime {
+    if instant >= now {
+        system_now + (instant - now)
+    } else {
+        system_now - (now - instant)
+    }
+}
+
+/// Distinguishes an unknown kid from not having any JWKS to look it up in.
+pub(crate) fn jwks_unavailable<I>(
+    settings: &DecoderSettings,
+    url_to_jwks: &HashMap<Url, JwksResult<I>>,
+) -> Option<JwtDecoderError> {
+    if url_to_jwks.values().any(|result| result.jwks.is_ok()) {
+        return None;
+    }
+    Some(JwtDecoderError::new_jwks_unavailable(format!(
+        "None of the {} JWKS URLs could be loaded",
+        settings.jwks_urls.len()
+    )))
+}
+
+pub(crate) fn token_kid(token: &str) -> Result<String, JwtDecoderError> {
+    let header = decode_header(token)
+        .map_err(|err| JwtDecoderError::new_header_parsing_failed(err.to_string()))?;
+    header.kid.ok_or(JwtDecoderError::new_missing_key_id())
+}
+
+/// Validates the token with the key, requiring the token's algorithm to be one of the allowed
+/// algorithms, of the key's family, and the JWK's `alg` if it declares one.
+pub(crate) fn validate_token(
+    token: &str,
+    key: &VerificationKey,
+    validation: &Validation,
+) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
+    // `decode` requires every allowed algorithm to belong to the key's family, so narrow them to
+    // the token's own to allow keys of different types in one JWKS.
+    let header = decode_header(token)
+        .map_err(|err| JwtDecoderError::new_header_parsing_failed(err.to_string()))?;
+    if !validation.algorithms.contains(&header.alg) {
+        return Err(JwtDecoderError::new_invalid_algorithm());
+    }
+    if let Some(algorithm) = key.algorithm {
+        if algorithm != key_algorithm(header.alg) {
+            return Err(JwtDecoderError::new_invalid_algorithm());
+        }
+    }
+    let mut validation = validation.clone();
+    validation.algorithms = vec![header.alg];
+    decode(token, &key.decoding_key, &validation).map_err(JwtDecoderError::from)
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::HS256 => KeyAlgorithm::HS256,
+        Algorithm::HS384 => KeyAlgorithm::HS384,
+        Algorithm::HS512 => KeyAlgorithm::HS512,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::ES384 => KeyAlgorithm::ES384,
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::RS384 => KeyAlgorithm::RS384,
+        Algorithm::RS512 => KeyAlgorithm::RS512,
+        Algorithm::PS256 => KeyAlgorithm::PS256,
+        Algorithm::PS384 => KeyAlgorithm::PS384,
+        Algorithm::PS512 => KeyAlgorithm::PS512,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{
+        jwk::{
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet, OctetKeyParameters, OctetKeyType, PublicKeyUse,
+            RSAKeyParameters, RSAKeyType,
+        },
+        Algorithm,
+    };
+    use rcgen::KeyPair;
+    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
+    use url::Url;
+
+    use crate::{config, error::JwtDecoderErrorReason, tokio::JwtDecoder};
+
+    fn jwk(kid: Option<&str>, algorithm: AlgorithmParameters) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                key_id: kid.map(ToOwned::to_owned),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+
+    #[tokio::test]
+    async fn jwks_limits() {
+        let ec_key = KeyPair::generate().unwrap();
+        let point = ec_key.public_key_raw();
+        let ec = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+            key_type: EllipticCurveKeyType::EC,
+            curve: EllipticCurve::P256,
+            x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+        });
+        let rsa_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let small_rsa = AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(rsa_key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(rsa_key.e().to_bytes_be()),
+        });
+        let small_oct = AlgorithmParameters::OctetKey(OctetKeyParameters {
+            key_type: OctetKeyType::Octet,
+            value: URL_SAFE_NO_PAD.encode([7u8; 16]),
+        });
+        let mut encryption = jwk(Some("encryption"), ec.clone());
+        encryption.common.public_key_use = Some(PublicKeyUse::Encryption);
+
+        let keys = vec![
+            jwk(Some("ec"), ec.clone()),
+            jwk(Some("small-rsa"), small_rsa),
+            jwk(Some("small-oct"), small_oct),
+            jwk(None, ec.clone()),
+            encryption,
+        ];
+        let mut too_many = keys.clone();
+        too_many.push(jwk(Some("one-too-many"), ec));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, data: Vec<u8>| {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            Url::from_file_path(path).unwrap()
+        };
+        let screened = write(
+            "screened.jwks",
+            serde_json::to_vec(&JwkSet { keys }).unwrap(),
+        );
+        let too_many = write(
+            "too-many.jwks",
+            serde_json::to_vec(&JwkSet { keys: too_many }).unwrap(),
+        );
+        let too_large = write("too-large.jwks", vec![b' '; 64 * 1024]);
+
+        let jwt_decoder = JwtDecoder::new(config::JwtDecoder {
+            jwks_urls: vec![screened.clone(), too_many, too_large],
+            algorithms: vec![Algorithm::ES256],
+            required_spec_claims: vec![],
+            valid_audiences: vec![],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: config::JwksLimits {
+                max_document_bytes: Some(32 * 1024),
+                max_keys: Some(5),
+                min_rsa_bits: None,
+                min_hmac_bytes: None,
+            },
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        })
+        .unwrap();
+
+        assert!(jwt_decoder
+            .status()
+            .iter()
+            .all(|status| status.last_fetch.is_none()));
+        let kids = jwt_decoder
+            .keys()
+            .await
+            .into_iter()
+            .map(|key| key.kid)
+            .collect::<Vec<_>>();
+        assert_eq!(kids, vec!["ec".to_owned()]);
+
+        let status = jwt_decoder.status();
+        assert_eq!(status[0].url, screened);
+        assert!(status[0].error.is_none());
+        assert_eq!(status[0].contributed_kids, vec!["ec".to_owned()]);
+        assert_eq!(
+            status[0]
+                .rejected
+                .iter()
+                .map(|rejected| (
+                    rejected.kid.as_deref(),
+                    JwtDecoderErrorReason::from(&rejected.error)
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("small-rsa"), JwtDecoderErrorReason::UnsupportedJwk),
+                (Some("small-oct"), JwtDecoderErrorReason::UnsupportedJwk),
+                (None, JwtDecoderErrorReason::UnsupportedJwk),
+                (Some("encryption"), JwtDecoderErrorReason::UnsupportedJwk),
+            ]
+        );
+        for status in &status[1..] {
+            assert!(!status.is_healthy());
+            assert!(
+                matches!(&status.error, Some(err) if err.to_string().contains("exceed")),
+                "{status:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let ec_key = KeyPair::generate().unwrap();
+        let point = ec_key.public_key_raw();
+        let ec = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+            key_type: EllipticCurveKeyType::EC,
+            curve: EllipticCurve::P256,
+            x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, keys: Vec<Jwk>| {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, serde_json::to_vec(&JwkSet { keys }).unwrap()).unwrap();
+            Url::from_file_path(path).unwrap()
+        };
+        let primary = write("primary.jwks", vec![jwk(Some("a"), ec.clone())]);
+        let secondary = write(
+            "secondary.jwks",
+            vec![jwk(Some("a"), ec.clone()), jwk(Some("b"), ec)],
+        );
+        let missing = Url::from_file_path(temp_dir.path().join("missing.jwks")).unwrap();
+
+        let jwt_decoder = JwtDecoder::new(config::JwtDecoder {
+            jwks_urls: vec![primary.clone(), secondary.clone(), missing],
+            algorithms: vec![Algorithm::ES256],
+            required_spec_claims: vec![],
+            valid_audiences: vec![],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        })
+        .unwrap();
+        jwt_decoder.keys().await;
+
+        let status = jwt_decoder.status();
+        assert!(status[0].is_healthy());
+        assert_eq!(status[0].contributed_kids, vec!["a".to_owned()]);
+        assert!(status[0].shadowed.is_empty());
+        assert!(status[0].last_fetch.unwrap() < status[0].expiration.unwrap());
+        assert_eq!(status[1].contributed_kids, vec!["b".to_owned()]);
+        assert_eq!(
+            status[1].shadowed,
+            vec![super::ShadowedJwk {
+                kid: "a".to_owned(),
+                by: primary,
+            }]
+        );
+        assert!(status[2].last_fetch.is_some());
+        assert_eq!(
+            status[2].error.as_ref().map(JwtDecoderErrorReason::from),
+            Some(JwtDecoderErrorReason::JwksUnavailable)
+        );
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json[2]["error"]["code"], "JWKS_UNAVAILABLE");
+        assert!(json[1]["last_fetch"].is_f64());
+    }
+}
@@ -1,8 +1,18 @@
 use async_trait::async_trait;
 
+pub mod authorization;
+pub mod binding;
+pub mod blocking;
 pub mod config;
 pub mod error;
+pub mod keys;
+pub mod metrics;
+pub mod paseto;
+pub mod reload;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tokio;
+pub mod x5c;
 
 use error::JwtDecoderError;
 use jsonwebtoken::TokenData;
@@ -10,4 +20,33 @@ use jsonwebtoken::TokenData;
 #[async_trait]
 pub trait JwtDecode {
     async fn decode(&self, token: &str) -> Result<TokenData<serde_json::Value>, JwtDecoderError>;
+
+    /// Decodes the token and verifies its `cnf.x5t#S256` binding against the DER encoded client
+    /// certificate presented on the mTLS connection.
+    async fn decode_with_client_certificate(
+        &self,
+        token: &str,
+        client_certificate: Option<&[u8]>,
+    ) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
+        let token_data = self.decode(token).await?;
+        binding::verify_certificate_binding(&token_data.claims, client_certificate)?;
+        Ok(token_data)
+    }
+}
+
+/// A synchronous counterpart to [JwtDecode] for callers without an async runtime.
+pub trait JwtDecodeBlocking {
+    fn decode(&self, token: &str) -> Result<TokenData<serde_json::Value>, JwtDecoderError>;
+
+    /// Decodes the token and verifies its `cnf.x5t#S256` binding against the DER encoded client
+    /// certificate presented on the mTLS connection.
+    fn decode_with_client_certificate(
+        &self,
+        token: &str,
+        client_certificate: Option<&[u8]>,
+    ) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
+        let token_data = self.decode(token)?;
+        binding::verify_certificate_binding(&token_data.claims, client_certificate)?;
+        Ok(token_data)
+    }
 }
@@ -0,0 +1,479 @@
+//! Verification of PASETO `v4.public` tokens signed with Ed25519 keys loaded from the same JWKS
+//! sources, with the same caching and claim validation rules, as [crate::tokio::JwtDecoder].
+//!
+//! A token's footer must be a JSON object whose `kid` names an `OKP` key in one of the JWKS. The
+//! `exp`, `nbf` and `iat` claims are RFC 3339 timestamps as required by PASETO.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::DateTime;
+use jsonwebtoken::{crypto, jwk::KeyAlgorithm, Algorithm, Validation};
+use serde_json::{Map, Value};
+
+use crate::{
+    config,
+    error::JwtDecoderError,
+    keys::VerificationKey,
+    metrics::{self, TokenFormat},
+    tokio::JwtDecoder,
+};
+
+const HEADER: &str = "v4.public.";
+const SIGNATURE_LEN: usize = 64;
+
+/// The verified claims and footer of a PASETO.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasetoData {
+    pub claims: Value,
+    pub footer: Value,
+}
+
+#[async_trait]
+pub trait PasetoDecode {
+    async fn decode(&self, token: &str) -> Result<PasetoData, JwtDecoderError>;
+}
+
+/// A [PasetoDecode] implementation for tokio. The config's `algorithms` are ignored since
+/// `v4.public` always uses Ed25519.
+pub struct PasetoDecoder {
+    keys: JwtDecoder,
+}
+
+impl PasetoDecoder {
+    pub fn new(config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
+        Ok(Self {
+            keys: JwtDecoder::new(config)?,
+        })
+    }
+
+    /// The underlying decoder, e.g. to list keys or report JWKS status.
+    pub fn jwt_decoder(&self) -> &JwtDecoder {
+        &self.keys
+    }
+}
+
+#[async_trait]
+impl PasetoDecode for PasetoDecoder {
+    async fn decode(&self, token: &str) -> Result<PasetoData, JwtDecoderError> {
+        let result = async {
+            let token = Token::parse(token)?;
+            let kid = token.kid()?;
+            let key = self
+                .keys
+                .jwk(&kid)
+                .await?
+                .ok_or_else(|| JwtDecoderError::new_unknown_kid(kid.to_owned()))?;
+            token.verify(&key)?;
+            let claims = serde_json::from_slice::<Value>(token.message())
+                .map_err(|err| JwtDecoderError::new_malformed_token(err.to_string()))?;
+            validate_claims(&claims, &self.keys.settings().validation)?;
+            Ok(PasetoData {
+                claims,
+                footer: token.footer_json()?,
+            })
+        }
+        .await;
+        metrics::record_decode(TokenFormat::Paseto, &result);
+        result
+    }
+}
+
+struct Token {
+    /// The message followed by its signature.
+    payload: Vec<u8>,
+    footer: Vec<u8>,
+}
+
+impl Token {
+    fn parse(token: &str) -> Result<Self, JwtDecoderError> {
+        let body = token.strip_prefix(HEADER).ok_or_else(|| {
+            JwtDecoderError::new_header_parsing_failed(format!("Expected `{HEADER}` header"))
+        })?;
+        let (payload, footer) = body.split_once('.').unwrap_or((body, ""));
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|err| JwtDecoderError::new_malformed_token(err.to_string()))
+        };
+        let payload = decode(payload)?;
+        if payload.len() < SIGNATURE_LEN {
+            return Err(JwtDecoderError::new_malformed_token(
+                "Payload is shorter than a signature".to_owned(),
+            ));
+        }
+        Ok(Self {
+            payload,
+            footer: decode(footer)?,
+        })
+    }
+
+    fn message(&self) -> &[u8] {
+        &self.payload[..self.payload.len() - SIGNATURE_LEN]
+    }
+
+    fn signature(&self) -> &[u8] {
+        &self.payload[self.payload.len() - SIGNATURE_LEN..]
+    }
+
+    fn footer_json(&self) -> Result<Value, JwtDecoderError> {
+        if self.footer.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&self.footer).map_err(|err| {
+            JwtDecoderError::new_header_parsing_failed(format!("Footer is not JSON: {err}"))
+        })
+    }
+
+    fn kid(&self) -> Result<String, JwtDecoderError> {
+        match self.footer_json()? {
+            Value::Object(mut footer) => match footer.remove("kid") {
+                Some(Value::String(kid)) => Ok(kid),
+                _ => Err(JwtDecoderError::new_missing_key_id()),
+            },
+            _ => Err(JwtDecoderError::new_missing_key_id()),
+        }
+    }
+
+    /// Verifies the Ed25519 signature over the pre-authentication encoding of the header, message,
+    /// footer and an empty implicit assertion.
+    fn verify(&self, key: &VerificationKey) -> Result<(), JwtDecoderError> {
+        if key.key_type != "OKP"
+            || key
+                .algorithm
+                .is_some_and(|algorithm| algorithm != KeyAlgorithm::EdDSA)
+        {
+            return Err(JwtDecoderError::new_invalid_algorithm());
+        }
+        let message = pae(&[HEADER.as_bytes(), self.message(), &self.footer, &[]]);
+        let signature = URL_SAFE_NO_PAD.encode(self.signature());
+        match crypto::verify(&signature, &message, &key.decoding_key, Algorithm::EdDSA)? {
+            true => Ok(()),
+            false => Err(JwtDecoderError::new_invalid_signature()),
+        }
+    }
+}
+
+/// Pre-authentication encoding, which length-prefixes each piece so that pieces cannot be
+/// shifted between one another.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut output = (pieces.len() as u64).to_le_bytes().to_vec();
+    for piece in pieces {
+        output.extend((piece.len() as u64).to_le_bytes());
+        output.extend(*piece);
+    }
+    output
+}
+
+/// Applies the same rules as [jsonwebtoken] applies to JWT claims.
+fn validate_claims(claims: &Value, validation: &Validation) -> Result<(), JwtDecoderError> {
+    let claims = claims.as_object().ok_or_else(|| {
+        JwtDecoderError::new_malformed_token("Claims are not a JSON object".to_owned())
+    })?;
+
+    for claim in &validation.required_spec_claims {
+        if matches!(claim.as_str(), "exp" | "nbf" | "aud" | "iss" | "sub")
+            && !claims.contains_key(claim)
+        {
+            return Err(JwtDecoderError::new_missing_required_claim(
+                claim.to_owned(),
+            ));
+        }
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let leeway = validation.leeway as i64;
+    if validation.validate_exp {
+        if let Some(exp) = timestamp(claims, "exp")? {
+            if exp - (validation.reject_tokens_expiring_in_less_than as i64) < now - leeway {
+                return Err(JwtDecoderError::new_token_expired());
+            }
+        }
+    }
+    if validation.validate_nbf {
+        if let Some(nbf) = timestamp(claims, "nbf")? {
+            if nbf > now + leeway {
+                return Err(JwtDecoderError::new_token_not_yet_valid());
+            }
+        }
+    }
+    // Parsed only to reject malformed values.
+    timestamp(claims, "iat")?;
+
+    if let (Some(sub), Some(valid_sub)) = (claims.get("sub"), &validation.sub) {
+        if sub.as_str() != Some(valid_sub) {
+            return Err(JwtDecoderError::new_bad_subject());
+        }
+    }
+    if let (Some(iss), Some(valid_issuers)) = (claims.get("iss"), &validation.iss) {
+        if !any_of(iss, |iss| valid_issuers.contains(iss)) {
+            return Err(JwtDecoderError::new_bad_issuer());
+        }
+    }
+    if validation.validate_aud {
+        let valid = match (claims.get("aud"), &validation.aud) {
+            (Some(_), None) => false,
+            (Some(aud), Some(valid_audiences)) => any_of(aud, |aud| valid_audiences.contains(aud)),
+            (None, _) => true,
+        };
+        if !valid {
+            return Err(JwtDecoderError::new_bad_audience());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a claim that is a string or an array of strings has any value matching.
+fn any_of(claim: &Value, matches: impl Fn(&str) -> bool) -> bool {
+    match claim {
+        Value::String(value) => matches(value),
+        Value::Array(values) => values.iter().filter_map(Value::as_str).any(matches),
+        _ => false,
+    }
+}
+
+/// The UNIX time of an RFC 3339 claim.
+fn timestamp(claims: &Map<String, Value>, claim: &str) -> Result<Option<i64>, JwtDecoderError> {
+    let Some(value) = claims.get(claim) else {
+        return Ok(None);
+    };
+    value
+        .as_str()
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|value| Some(value.timestamp()))
+        .ok_or_else(|| {
+            JwtDecoderError::new_malformed_token(format!(
+                "Claim `{claim}` is not an RFC 3339 timestamp"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use jsonwebtoken::{
+        crypto,
+        jwk::{
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet, OctetKeyPairParameters, OctetKeyPairType,
+        },
+        Algorithm, DecodingKey, EncodingKey,
+    };
+    use rcgen::{KeyPair, PKCS_ED25519};
+    use serde_json::{json, Value};
+    use url::Url;
+
+    use super::{pae, PasetoDecode, PasetoDecoder, Token, HEADER};
+    use crate::{config, error::JwtDecoderError, keys::VerificationKey};
+
+    fn sign(key: &EncodingKey, claims: &Value, footer: &[u8]) -> String {
+        let message = serde_json::to_vec(claims).unwrap();
+        let signature = crypto::sign(
+            &pae(&[HEADER.as_bytes(), &message, footer, &[]]),
+            key,
+            Algorithm::EdDSA,
+        )
+        .unwrap();
+        let mut payload = message;
+        payload.extend(URL_SAFE_NO_PAD.decode(signature).unwrap());
+        let mut token = format!("{HEADER}{}", URL_SAFE_NO_PAD.encode(payload));
+        if !footer.is_empty() {
+            token = format!("{token}.{}", URL_SAFE_NO_PAD.encode(footer));
+        }
+        token
+    }
+
+    fn rfc3339(time: SystemTime) -> String {
+        let secs = time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        DateTime::<Utc>::from_timestamp(secs, 0)
+            .unwrap()
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    #[tokio::test]
+    async fn verifies_v4_public() {
+        let ed_key = KeyPair::generate_for(&PKCS_ED25519).unwrap();
+        let ec_key = KeyPair::generate().unwrap();
+        let point = ec_key.public_key_raw();
+        let jwk = |kid: &str, algorithm| Jwk {
+            common: CommonParameters {
+                key_id: Some(kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm,
+        };
+        let jwks = JwkSet {
+            keys: vec![
+                jwk(
+                    "ed",
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: URL_SAFE_NO_PAD.encode(ed_key.public_key_raw()),
+                    }),
+                ),
+                jwk(
+                    "ec",
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                        y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+                    }),
+                ),
+            ],
+        };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let jwks_file = temp_dir.path().join("keys.jwks");
+        std::fs::write(&jwks_file, serde_json::to_vec(&jwks).unwrap()).unwrap();
+
+        let paseto_decoder = PasetoDecoder::new(config::JwtDecoder {
+            jwks_urls: vec![Url::from_file_path(&jwks_file).unwrap()],
+            algorithms: vec![],
+            required_spec_claims: vec!["exp".to_owned(), "sub".to_owned()],
+            valid_audiences: vec!["some-users".to_owned()],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        })
+        .unwrap();
+
+        let ed_encoding_key = EncodingKey::from_ed_der(&ed_key.serialize_der());
+        let exp = rfc3339(SystemTime::now() + Duration::from_secs(300));
+        let claims = json!({ "sub": "user@example.com", "aud": "some-users", "exp": exp });
+        let footer = br#"{"kid":"ed"}"#;
+
+        let paseto_data = paseto_decoder
+            .decode(&sign(&ed_encoding_key, &claims, footer))
+            .await
+            .unwrap();
+        assert_eq!(paseto_data.claims, claims);
+        assert_eq!(paseto_data.footer, json!({ "kid": "ed" }));
+
+        let any_audience = json!({
+            "sub": "user@example.com",
+            "aud": ["api", "some-users"],
+            "exp": exp,
+        });
+        assert!(paseto_decoder
+            .decode(&sign(&ed_encoding_key, &any_audience, footer))
+            .await
+            .is_ok());
+
+        let tampered = {
+            let token = sign(&ed_encoding_key, &claims, footer);
+            let other = sign(&ed_encoding_key, &claims, br#"{"kid":"ed","x":1}"#);
+            let (body, _) = token.rsplit_once('.').unwrap();
+            let (_, other_footer) = other.rsplit_once('.').unwrap();
+            format!("{body}.{other_footer}")
+        };
+        let expired = json!({
+            "sub": "user@example.com",
+            "exp": rfc3339(SystemTime::now() - Duration::from_secs(300)),
+        });
+        for (token, err) in [
+            (tampered, JwtDecoderError::new_invalid_signature()),
+            (
+                sign(&ed_encoding_key, &expired, footer),
+                JwtDecoderError::new_token_expired(),
+            ),
+            (
+                sign(
+                    &ed_encoding_key,
+                    &json!({ "sub": "user@example.com", "aud": "nobody", "exp": exp }),
+                    footer,
+                ),
+                JwtDecoderError::new_bad_audience(),
+            ),
+            (
+                sign(
+                    &ed_encoding_key,
+                    &json!({ "sub": "user@example.com", "aud": ["api", "web"], "exp": exp }),
+                    footer,
+                ),
+                JwtDecoderError::new_bad_audience(),
+            ),
+            (
+                sign(
+                    &ed_encoding_key,
+                    &json!({ "sub": "user@example.com" }),
+                    footer,
+                ),
+                JwtDecoderError::new_missing_required_claim("exp".to_owned()),
+            ),
+            (
+                sign(&ed_encoding_key, &claims, b""),
+                JwtDecoderError::new_missing_key_id(),
+            ),
+            (
+                sign(&ed_encoding_key, &claims, br#"{"kid":"other"}"#),
+                JwtDecoderError::new_unknown_kid("other".to_owned()),
+            ),
+            (
+                sign(&ed_encoding_key, &claims, br#"{"kid":"ec"}"#),
+                JwtDecoderError::new_invalid_algorithm(),
+            ),
+        ] {
+            assert_eq!(paseto_decoder.decode(&token).await.err(), Some(err));
+        }
+
+        assert!(matches!(
+            paseto_decoder
+                .decode(&sign(&ed_encoding_key, &claims, footer).replacen("v4", "v3", 1))
+                .await,
+            Err(JwtDecoderError::HeaderParsingFailed { .. })
+        ));
+        assert!(matches!(
+            paseto_decoder
+                .decode(&sign(
+                    &ed_encoding_key,
+                    &json!({ "sub": "user@example.com", "exp": 1700000000 }),
+                    footer
+                ))
+                .await,
+            Err(JwtDecoderError::MalformedToken { .. })
+        ));
+    }
+
+    /// Test vector 4-S-1 from the PASETO specification.
+    #[test]
+    fn verifies_test_vector() {
+        let public_key = (0..32)
+            .map(|i| {
+                u8::from_str_radix(
+                    &"1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2"
+                        [2 * i..2 * i + 2],
+                    16,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let token = Token::parse(
+            "v4.public.eyJkYXRhIjoidGhpcyBpcyBhIHNpZ25lZCBtZXNzYWdlIiwiZXhwIjoiMjAyMi0wMS0wMVQwMDow\
+             MDowMCswMDowMCJ9bg_XBBzds8lTZShVlwwKSgeKpLT3yukTw6JUz3W4h_ExsQV-P0V54zemZDcAxFaSeef1Q\
+             lXEFtkqxT1ciiQEDA",
+        )
+        .unwrap();
+        let key = VerificationKey {
+            decoding_key: DecodingKey::from_ed_der(&public_key),
+            key_type: "OKP".to_owned(),
+            algorithm: None,
+        };
+        assert_eq!(token.verify(&key), Ok(()));
+        assert_eq!(
+            serde_json::from_slice::<Value>(token.message()).unwrap(),
+            json!({ "data": "this is a signed message", "exp": "2022-01-01T00:00:00+00:00" })
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! A [JwtDecoderHandle] whose configuration can be replaced while in use.
+
+use std::{
+    pin::pin,
+    sync::{Arc, RwLock, Weak},
+};
+
+use appbiotic_data_url_resource::UrlResourceContent;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use jsonwebtoken::TokenData;
+use tokio::{sync::Mutex, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{config, error::JwtDecoderError, tokio::JwtDecoder, JwtDecode};
+
+/// A cloneable [JwtDecode] handle to a [JwtDecoder] that is rebuilt whenever its config is
+/// updated, either explicitly with [JwtDecoderHandle::update] or by
+/// [JwtDecoderHandle::watch]ing config updates. JWKS already loaded for URLs present in both
+/// the old and new config are carried over until they expire.
+#[derive(Clone)]
+pub struct JwtDecoderHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    decoder: RwLock<Arc<JwtDecoder>>,
+    // Serializes updates so that a slower update cannot overwrite a newer one.
+    updating: Mutex<()>,
+    // Cancelled once every handle is dropped, ending the watch tasks.
+    closed: CancellationToken,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.closed.cancel();
+    }
+}
+
+impl JwtDecoderHandle {
+    pub fn new(config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
+        Ok(Self {
+            inner: Arc::new(Inner {
+                decoder: RwLock::new(Arc::new(JwtDecoder::new(config)?)),
+                updating: Mutex::new(()),
+                closed: CancellationToken::new(),
+            }),
+        })
+    }
+
+    /// The decoder for the current config.
+    pub fn current(&self) -> Arc<JwtDecoder> {
+        self.inner.current()
+    }
+
+    /// Replaces the config, leaving the current one in place if the new one is invalid.
+    pub async fn update(&self, config: config::JwtDecoder) -> Result<(), JwtDecoderError> {
+        self.inner.update(config).await
+    }
+
+    /// Spawns a task updating the decoder with each [config::JwtDecoder] streamed, such as by
+    /// [appbiotic_data_url_resource::tokio::UrlResource::subscribe] to a resource with background
+    /// refresh or a file watch. Configs are read as YAML or TOML when the content type or URL
+    /// extension says so, and as JSON otherwise. The task ends with the stream or once all
+    /// handles are dropped.
+    pub fn watch<S>(&self, updates: S) -> JoinHandle<()>
+    where
+        S: Stream<Item = UrlResourceContent> + Send + 'static,
+    {
+        let inner = Arc::downgrade(&self.inner);
+        let closed = self.inner.closed.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = watch(inner, updates) => {}
+                _ = closed.cancelled() => {}
+            }
+        })
+    }
+}
+
+impl Inner {
+    fn current(&self) -> Arc<JwtDecoder> {
+        match self.decoder.read() {
+            Ok(decoder) => decoder.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    async fn update(&self, config: config::JwtDecoder) -> Result<(), JwtDecoderError> {
+        let _updating = self.updating.lock().await;
+        let decoder = Arc::new(self.current().reconfigured(config)?);
+        match self.decoder.write() {
+            Ok(mut current) => *current = decoder,
+            Err(poisoned) => *poisoned.into_inner() = decoder,
+        }
+        Ok(())
+    }
+}
+
+async fn watch(inner: Weak<Inner>, updates: impl Stream<Item = UrlResourceContent>) {
+    let mut updates = pin!(updates);
+    while let Some(content) = updates.next().await {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        match parse_config(&content) {
+            Ok(config) => match inner.update(config).await {
+                Ok(()) => info!(
+                    integrity = content.integrity(),
+                    "Reloaded JwtDecoder config"
+                ),
+                Err(err) => warn!(error = %err, "Rejected JwtDecoder config"),
+            },
+            Err(err) => warn!(error = %err, "Failed to parse JwtDecoder config"),
+        }
+    }
+}
+
+fn parse_config(content: &UrlResourceContent) -> Result<config::JwtDecoder, JwtDecoderError> {
+    let content_type = content.metadata.content_type.as_deref().unwrap_or_default();
+    let path = content.metadata.url.path();
+    let is = |format: &str, extensions: &[&str]| {
+        content_type.contains(format)
+            || extensions.iter().any(|extension| path.ends_with(extension))
+    };
+    fn invalid(err: impl ToString) -> JwtDecoderError {
+        JwtDecoderError::new_failed_precondition(err.to_string())
+    }
+    if is("yaml", &[".yaml", ".yml"]) {
+        serde_yaml::from_slice(&content.data).map_err(invalid)
+    } else if is("toml", &[".toml"]) {
+        let data = std::str::from_utf8(&content.data).map_err(invalid)?;
+        toml::from_str(data).map_err(invalid)
+    } else {
+        serde_json::from_slice(&content.data).map_err(invalid)
+    }
+}
+
+#[async_trait]
+impl JwtDecode for JwtDecoderHandle {
+    async fn decode(&self, token: &str) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
+        self.current().decode(token).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use appbiotic_data_url_resource::{
+        config::UrlResource as UrlResourceConfig, tokio::UrlResource,
+    };
+    use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{
+        encode,
+        jwk::{
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet,
+        },
+        Algorithm, EncodingKey, Header,
+    };
+    use rcgen::KeyPair;
+    use serde_json::json;
+    use url::Url;
+
+    use crate::{config, error::JwtDecoderError, JwtDecode};
+
+    use super::JwtDecoderHandle;
+
+    fn jwk(kid: &str, key: &KeyPair) -> Jwk {
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let point = key.public_key_raw();
+        Jwk {
+            common: CommonParameters {
+                key_id: Some(kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }),
+        }
+    }
+
+    fn token(kid: &str, key: &KeyPair, aud: &str) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid.to_owned());
+        encode(
+            &header,
+            &json!({ "sub": "user@example.com", "aud": aud, "exp": u64::MAX / 2 }),
+            &EncodingKey::from_ec_der(&key.serialize_der()),
+        )
+        .unwrap()
+    }
+
+    fn config(jwks_urls: Vec<Url>, audience: &str) -> config::JwtDecoder {
+        config::JwtDecoder {
+            jwks_urls,
+            algorithms: vec![Algorithm::ES256],
+            required_spec_claims: vec!["sub".to_owned(), "aud".to_owned()],
+            valid_audiences: vec![audience.to_owned()],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        }
+    }
+
+    #[tokio::test]
+    async fn reloads_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let jwks_a = temp_dir.path().join("a.jwks");
+        let jwks_b = temp_dir.path().join("b.jwks");
+        let config_file = temp_dir.path().join("config.yaml");
+        let (url_a, url_b) = (
+            Url::from_file_path(&jwks_a).unwrap(),
+            Url::from_file_path(&jwks_b).unwrap(),
+        );
+
+        let key_a = KeyPair::generate().unwrap();
+        let key_b = KeyPair::generate().unwrap();
+        let write_jwks = |path: &std::path::Path, keys: Vec<Jwk>| {
+            std::fs::write(path, serde_json::to_vec(&JwkSet { keys }).unwrap()).unwrap()
+        };
+        write_jwks(&jwks_a, vec![jwk("a", &key_a)]);
+        write_jwks(&jwks_b, vec![jwk("b", &key_b)]);
+
+        let handle = JwtDecoderHandle::new(config(vec![url_a.clone()], "one")).unwrap();
+        assert!(handle.decode(&token("a", &key_a, "one")).await.is_ok());
+
+        // Keys already loaded from `a` survive the update even though `a` no longer has them.
+        write_jwks(&jwks_a, vec![]);
+        handle
+            .update(config(vec![url_b.clone(), url_a.clone()], "two"))
+            .await
+            .unwrap();
+        assert_eq!(
+            handle.decode(&token("a", &key_a, "one")).await.err(),
+            Some(JwtDecoderError::new_bad_audience())
+        );
+        assert!(handle.decode(&token("a", &key_a, "two")).await.is_ok());
+        assert!(handle.decode(&token("b", &key_b, "two")).await.is_ok());
+
+        // An invalid config leaves the current one in place.
+        let mut invalid = config(vec![url_b.clone()], "three");
+        invalid.kind = config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {});
+        assert!(handle.update(invalid).await.is_err());
+        assert!(handle.decode(&token("b", &key_b, "two")).await.is_ok());
+
+        let write_config = |config: config::JwtDecoder| {
+            std::fs::write(&config_file, serde_yaml::to_string(&config).unwrap()).unwrap()
+        };
+        let reloaded = |aud: &'static str| {
+            let handle = handle.clone();
+            let token = token("b", &key_b, aud);
+            async move {
+                for _ in 0..100 {
+                    if handle.decode(&token).await.is_ok() {
+                        return true;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                false
+            }
+        };
+
+        // Updates are pushed by the resource's background refresh.
+        write_config(config(vec![url_b.clone()], "three"));
+        let resource = UrlResource::new(UrlResourceConfig {
+            cache_ttl: Some(Duration::from_millis(50)),
+            background_refresh: Some(true),
+            ..UrlResourceConfig::new(Url::from_file_path(&config_file).unwrap())
+        })
+        .unwrap();
+        let watch = handle.watch(resource.subscribe().await.unwrap());
+        assert!(reloaded("three").await);
+        assert!(handle.decode(&token("a", &key_a, "three")).await.is_err());
+
+        write_config(config(vec![url_b], "four"));
+        assert!(reloaded("four").await);
+
+        // The watch ends with the handles even while the resource is still in use.
+        drop(handle);
+        tokio::time::timeout(Duration::from_secs(1), watch)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+//! An in-process test issuer for consumers of [JwtDecode] and [JwtDecodeBlocking].
+//!
+//! ```
+//! use appbiotic_auth_jwt_decoder::{testing::TestIssuer, tokio::JwtDecoder, JwtDecode};
+//!
+//! # tokio::runtime::Builder::new_current_thread()
+//! #     .enable_time()
+//! #     .build()
+//! #     .unwrap()
+//! #     .block_on(async {
+//! let issuer = TestIssuer::new();
+//! let jwt_decoder = JwtDecoder::new(issuer.config()).unwrap();
+//! let token = issuer.es256().token().subject("alice").build();
+//! let token_data = jwt_decoder.decode(&token).await.unwrap();
+//! assert_eq!(token_data.claims["sub"], "alice");
+//! # });
+//! ```
+//!
+//! The keys are pregenerated and published in this crate, so they must never be trusted outside
+//! of tests.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use base64::engine::{general_purpose::STANDARD, Engine};
+use jsonwebtoken::{
+    encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, EncodingKey, Header, TokenData,
+};
+use serde_json::{Map, Value};
+use tempfile::TempDir;
+use url::Url;
+
+use crate::{config, error::JwtDecoderError, JwtDecode, JwtDecodeBlocking};
+
+pub const ISSUER: &str = "https://issuer.example.com";
+pub const AUDIENCE: &str = "test-audience";
+pub const SUBJECT: &str = "user@example.com";
+
+const RS256_PEM: &str = include_str!("testing/rs256.pem");
+const RS256_N: &str = "zQvw_RPvgSNvwGjKeIqOxnLYYEX9hKr7e9n9CAHv0MJWF8PfY_VOH5uGD1w12vrE6CsM9T4Dp2NyH0pyzSezAQzg1UtKnlSDs4Py1mLdY_SXxxM6VfPN7gGGmg5ooYdP-XSEgqW5rArNQs6_2f2EPwPx1Wtby1MBy04V4ZOivrPmtWDtCum21-I_q7k83JOI8d4JNhqM1-rY_Ies4TUjfhxhscx4oximQyE469qiRMALL0RUYLBdjucKhiUO-2yBGHDLOs1iuMl_-_ELmY0ENMZNU6lC0G-hzt2QIW-dM_EiyMEuBvJXkytrar-4YP5zq0ITxTR4yGyy-i4giV19MQ";
+const RS256_E: &str = "AQAB";
+const ES256_PEM: &str = include_str!("testing/es256.pem");
+const ES256_X: &str = "VycSpHM3LdZeHqLcOa2RDKtObSTCQ6-wgZiqM0VWR8o";
+const ES256_Y: &str = "5-ubSO7C16gmBh77W6ANusAAH0p-PlT8JYvhmCkTjV8";
+const ED_DSA_PEM: &str = include_str!("testing/ed_dsa.pem");
+const ED_DSA_X: &str = "1LrcyI61acjCbphiqcVmKudvNw5ASagaKE9lJyjubQk";
+
+/// A pregenerated signing key and its public JWK.
+#[derive(Clone, Debug)]
+pub struct TestKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    private_key_pem: &'static str,
+    parameters: AlgorithmParameters,
+}
+
+impl TestKey {
+    pub fn rs256() -> Self {
+        Self {
+            kid: "test-rs256".to_owned(),
+            algorithm: Algorithm::RS256,
+            private_key_pem: RS256_PEM,
+            parameters: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: RS256_N.to_owned(),
+                e: RS256_E.to_owned(),
+            }),
+        }
+    }
+
+    pub fn es256() -> Self {
+        Self {
+            kid: "test-es256".to_owned(),
+            algorithm: Algorithm::ES256,
+            private_key_pem: ES256_PEM,
+            parameters: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: ES256_X.to_owned(),
+                y: ES256_Y.to_owned(),
+            }),
+        }
+    }
+
+    pub fn ed_dsa() -> Self {
+        Self {
+            kid: "test-ed-dsa".to_owned(),
+            algorithm: Algorithm::EdDSA,
+            private_key_pem: ED_DSA_PEM,
+            parameters: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: ED_DSA_X.to_owned(),
+            }),
+        }
+    }
+
+    pub fn with_kid(mut self, kid: impl Into<String>) -> Self {
+        self.kid = kid.into();
+        self
+    }
+
+    /// The PKCS#8 PEM encoded private key.
+    pub fn private_key_pem(&self) -> &'static str {
+        self.private_key_pem
+    }
+
+    pub fn encoding_key(&self) -> EncodingKey {
+        let pem = self.private_key_pem.as_bytes();
+        match self.algorithm {
+            Algorithm::ES256 => EncodingKey::from_ec_pem(pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(pem),
+            _ => EncodingKey::from_rsa_pem(pem),
+        }
+        .expect("pregenerated test key is valid")
+    }
+
+    pub fn jwk(&self) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match self.algorithm {
+                    Algorithm::ES256 => KeyAlgorithm::ES256,
+                    Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                key_id: Some(self.kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm: self.parameters.clone(),
+        }
+    }
+
+    /// Starts a token signed by this key with the default [ISSUER], [AUDIENCE] and [SUBJECT]
+    /// claims, issued now and expiring in an hour.
+    pub fn token(&self) -> TokenBuilder {
+        TokenBuilder::new(self.clone())
+    }
+}
+
+pub struct TokenBuilder {
+    key: TestKey,
+    kid: Option<String>,
+    claims: Map<String, Value>,
+}
+
+impl TokenBuilder {
+    fn new(key: TestKey) -> Self {
+        let now = now();
+        let mut claims = Map::new();
+        claims.insert("iss".to_owned(), ISSUER.into());
+        claims.insert("aud".to_owned(), AUDIENCE.into());
+        claims.insert("sub".to_owned(), SUBJECT.into());
+        claims.insert("iat".to_owned(), now.into());
+        claims.insert("exp".to_owned(), (now + 3600).into());
+        Self {
+            kid: Some(key.kid.to_owned()),
+            key,
+            claims,
+        }
+    }
+
+    /// Overrides the header `kid`, or omits it if `None`.
+    pub fn kid(mut self, kid: Option<&str>) -> Self {
+        self.kid = kid.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Sets a claim, or removes it if `Value::Null`.
+    pub fn claim(mut self, name: &str, value: impl Into<Value>) -> Self {
+        match value.into() {
+            Value::Null => self.claims.remove(name),
+            value => self.claims.insert(name.to_owned(), value),
+        };
+        self
+    }
+
+    pub fn issuer(self, issuer: &str) -> Self {
+        self.claim("iss", issuer)
+    }
+
+    pub fn audience(self, audience: &str) -> Self {
+        self.claim("aud", audience)
+    }
+
+    pub fn subject(self, subject: &str) -> Self {
+        self.claim("sub", subject)
+    }
+
+    pub fn expires_in(self, duration: Duration) -> Self {
+        let exp = now() + duration.as_secs();
+        self.claim("exp", exp)
+    }
+
+    /// Sets `exp` well past the default validation leeway.
+    pub fn expired(self) -> Self {
+        let exp = now() - 3600;
+        self.claim("exp", exp)
+    }
+
+    pub fn not_before_in(self, duration: Duration) -> Self {
+        let nbf = now() + duration.as_secs();
+        self.claim("nbf", nbf)
+    }
+
+    pub fn build(self) -> String {
+        let mut header = Header::new(self.key.algorithm);
+        header.kid = self.kid;
+        encode(&header, &self.claims, &self.key.encoding_key()).expect("test token encodes")
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after the UNIX epoch")
+        .as_secs()
+}
+
+/// Test keys for every supported algorithm family with their [JwkSet] published to a temporary
+/// file, which is removed when the issuer is dropped.
+pub struct TestIssuer {
+    keys: Vec<TestKey>,
+    jwks_path: PathBuf,
+    _temp_dir: TempDir,
+}
+
+impl Default for TestIssuer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestIssuer {
+    pub fn new() -> Self {
+        Self::with_keys(vec![TestKey::rs256(), TestKey::es256(), TestKey::ed_dsa()])
+    }
+
+    pub fn with_keys(keys: Vec<TestKey>) -> Self {
+        let temp_dir = TempDir::new().expect("temp dir is created");
+        let jwks_path = temp_dir.path().join("jwks.json");
+        let issuer = Self {
+            keys,
+            jwks_path,
+            _temp_dir: temp_dir,
+        };
+        issuer.publish(issuer.keys.iter().map(TestKey::jwk).collect());
+        issuer
+    }
+
+    pub fn keys(&self) -> &[TestKey] {
+        &self.keys
+    }
+
+    pub fn key(&self, kid: &str) -> Option<&TestKey> {
+        self.keys.iter().find(|key| key.kid == kid)
+    }
+
+    pub fn rs256(&self) -> &TestKey {
+        self.by_algorithm(Algorithm::RS256)
+    }
+
+    pub fn es256(&self) -> &TestKey {
+        self.by_algorithm(Algorithm::ES256)
+    }
+
+    pub fn ed_dsa(&self) -> &TestKey {
+        self.by_algorithm(Algorithm::EdDSA)
+    }
+
+    fn by_algorithm(&self, algorithm: Algorithm) -> &TestKey {
+        self.keys
+            .iter()
+            .find(|key| key.algorithm == algorithm)
+            .unwrap_or_else(|| panic!("TestIssuer has no {algorithm:?} key"))
+    }
+
+    pub fn jwk_set(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(TestKey::jwk).collect(),
+        }
+    }
+
+    /// Replaces the published JWKS, e.g. to simulate key rotation.
+    pub fn publish(&self, keys: Vec<Jwk>) {
+        std::fs::write(
+            &self.jwks_path,
+            serde_json::to_vec(&JwkSet { keys }).expect("JWKS serializes"),
+        )
+        .expect("JWKS file is written");
+    }
+
+    /// A `file:` URL of the published JWKS.
+    pub fn jwks_file_url(&self) -> Url {
+        Url::from_file_path(&self.jwks_path).expect("temp path is absolute")
+    }
+
+    /// An in-memory `data:` URL of the JWKS of all keys.
+    pub fn jwks_data_url(&self) -> Url {
+        let jwks = serde_json::to_vec(&self.jwk_set()).expect("JWKS serializes");
+        Url::parse(&format!(
+            "data:application/json;base64,{}",
+            STANDARD.encode(jwks)
+        ))
+        .expect("data URL is valid")
+    }
+
+    /// A tokio decoder config accepting tokens from this issuer.
+    pub fn config(&self) -> config::JwtDecoder {
+        config::JwtDecoder {
+            jwks_urls: vec![self.jwks_file_url()],
+            algorithms: vec![Algorithm::RS256, Algorithm::ES256, Algorithm::EdDSA],
+            required_spec_claims: vec!["exp".to_owned(), "sub".to_owned()],
+            valid_audiences: vec![AUDIENCE.to_owned()],
+            valid_issuers: vec![ISSUER.to_owned()],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        }
+    }
+}
+
+/// A [JwtDecode] and [JwtDecodeBlocking] mock returning canned results per token.
+#[derive(Clone, Debug)]
+pub struct MockJwtDecoder {
+    results: HashMap<String, Result<Value, JwtDecoderError>>,
+    default: Result<Value, JwtDecoderError>,
+}
+
+impl MockJwtDecoder {
+    /// Returns the claims for every token not otherwise configured.
+    pub fn accepting(claims: Value) -> Self {
+        Self {
+            results: HashMap::new(),
+            default: Ok(claims),
+        }
+    }
+
+    /// Returns the error for every token not otherwise configured.
+    pub fn rejecting(err: JwtDecoderError) -> Self {
+        Self {
+            results: HashMap::new(),
+            default: Err(err),
+        }
+    }
+
+    pub fn with_token(mut self, token: &str, result: Result<Value, JwtDecoderError>) -> Self {
+        self.results.insert(token.to_owned(), result);
+        self
+    }
+
+    fn result(&self, token: &str) -> Result<TokenData<Value>, JwtDecoderError> {
+        let claims = self.results.get(token).unwrap_or(&self.default).clone()?;
+        Ok(TokenData {
+            header: Header::default(),
+            claims,
+        })
+    }
+}
+
+#[async_trait]
+impl JwtDecode for MockJwtDecoder {
+    async fn decode(&self, token: &str) -> Result<TokenData<Value>, JwtDecoderError> {
+        self.result(token)
+    }
+}
+
+impl JwtDecodeBlocking for MockJwtDecoder {
+    fn decode(&self, token: &str) -> Result<TokenData<Value>, JwtDecoderError> {
+        self.result(token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{
+        blocking, config, error::JwtDecoderError, tokio::JwtDecoder, JwtDecode, JwtDecodeBlocking,
+    };
+
+    use super::{MockJwtDecoder, TestIssuer};
+
+    #[tokio::test]
+    async fn issues_verifiable_tokens() {
+        let issuer = TestIssuer::new();
+        let jwt_decoder = JwtDecoder::new(issuer.config()).unwrap();
+
+        for key in issuer.keys() {
+            let token_data = jwt_decoder
+                .decode(&key.token().claim("scope", "read").build())
+                .await
+                .unwrap();
+            assert_eq!(token_data.claims["scope"], "read", "{:?}", key.algorithm);
+        }
+
+        let key = issuer.es256();
+        for (token, err) in [
+            (
+                key.token().expired().build(),
+                JwtDecoderError::new_token_expired(),
+            ),
+            (
+                key.token().audience("other").build(),
+                JwtDecoderError::new_bad_audience(),
+            ),
+            (
+                key.token().kid(Some("unknown")).build(),
+                JwtDecoderError::new_unknown_kid("unknown".to_owned()),
+            ),
+            (
+                key.token().kid(None).build(),
+                JwtDecoderError::new_missing_key_id(),
+            ),
+        ] {
+            assert_eq!(jwt_decoder.decode(&token).await.err(), Some(err));
+        }
+
+        let mut config = issuer.config();
+        config.jwks_urls = vec![issuer.jwks_data_url()];
+        config.kind = config::JwtDecoderKind::Blocking(config::BlockingJwtDecoder {});
+        let blocking_decoder = blocking::JwtDecoder::new(config).unwrap();
+        assert!(
+            JwtDecodeBlocking::decode(&blocking_decoder, &issuer.ed_dsa().token().build()).is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn mock_returns_canned_results() {
+        let mock = MockJwtDecoder::accepting(json!({ "sub": "alice" }))
+            .with_token("bad", Err(JwtDecoderError::new_invalid_signature()));
+
+        assert_eq!(
+            JwtDecode::decode(&mock, "good").await.unwrap().claims["sub"],
+            "alice"
+        );
+        assert_eq!(
+            JwtDecodeBlocking::decode(&mock, "bad").err(),
+            Some(JwtDecoderError::new_invalid_signature())
+        );
+    }
+}
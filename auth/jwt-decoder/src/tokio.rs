@@ -1,273 +1,306 @@
-use std::{
-    collections::HashMap, convert::AsRef, os::unix::fs::MetadataExt, path::Path, sync::Arc,
-    time::Duration,
-};
+use std::{collections::HashMap, convert::AsRef, sync::Arc, time::SystemTime};
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use cached::{Cached, CanExpire, TimedCache};
-use futures::future::join_all;
-use jsonwebtoken::{
-    decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, TokenData, Validation,
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::{
+    future::{join_all, BoxFuture, Shared},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
 };
+use jsonwebtoken::{jwk::JwkSet, TokenData};
 use tokio::{
-    fs::File,
-    io::AsyncReadExt,
-    sync::Mutex,
+    task::spawn_blocking,
     time::{timeout, Instant},
 };
-use tracing::{info_span, warn, Instrument};
+use tracing::{info_span, Instrument};
 use url::Url;
 
-use crate::{config, error::JwtDecoderError, JwtDecode};
+use crate::{
+    config,
+    error::JwtDecoderError,
+    keys::{
+        self, DecoderSettings, JwkInfo, JwksSourceStatus, Limits, ScreenedJwks, VerificationKey,
+    },
+    metrics::{self, TokenFormat},
+    JwtDecode,
+};
 
-pub struct JwtDecoder {
-    jwks_urls: Vec<Url>,
-    url_to_jwks: Arc<Mutex<HashMap<Url, JwksResult>>>,
-    kid_to_jwk: Arc<Mutex<TimedCache<String, JwkEntry>>>,
-    max_wait: Duration,
-    ttl: Duration,
-    validation: Validation,
-}
+type JwksResult = keys::JwksResult<Instant>;
+type State = keys::KeyState<Instant>;
+
+type Fetch = Shared<BoxFuture<'static, ()>>;
 
-#[derive(Clone)]
-struct JwksResult {
-    jwks: Result<JwkSet, JwtDecoderError>,
-    expiration: Instant,
+/// Loads the JWKS at a URL, replaceable so that tests can control when loads complete.
+type Load =
+    Arc<dyn Fn(&Url, &Limits) -> BoxFuture<'static, Result<JwkSet, JwtDecoderError>> + Send + Sync>;
+
+/// A [JwtDecode] implementation for tokio. Decodes read an immutable snapshot of the loaded keys
+/// and never wait on a lock held across I/O. Expired JWKS are refetched at most once at a time
+/// per URL, on a spawned task shared by every decode waiting on it, and each URL's keys are
+/// published as soon as its fetch completes.
+pub struct JwtDecoder {
+    inner: Arc<Inner>,
 }
 
-#[derive(Clone)]
-struct JwkEntry {
-    jwk: Arc<DecodingKey>,
-    expiration: Instant,
+struct Inner {
+    settings: DecoderSettings,
+    state: ArcSwap<State>,
+    fetches: DashMap<Url, Fetch>,
+    load: Load,
 }
 
-impl CanExpire for JwkEntry {
-    fn is_expired(&self) -> bool {
-        self.expiration < Instant::now()
-    }
+fn load_blocking(
+    url: &Url,
+    limits: &Limits,
+) -> BoxFuture<'static, Result<JwkSet, JwtDecoderError>> {
+    let (url, limits) = (url.to_owned(), limits.clone());
+    spawn_blocking(move || keys::load_jwks(&url, &limits))
+        .map(|loaded| {
+            loaded.unwrap_or_else(|err| Err(JwtDecoderError::new_internal_error(err.to_string())))
+        })
+        .boxed()
 }
 
 impl JwtDecoder {
     pub fn new(config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
-        let config::JwtDecoderKind::Tokio(_) = config.kind;
+        Self::with_load(config, Arc::new(load_blocking))
+    }
 
-        let validation = {
-            // NOTE: algorithm in `new` will be overwritten.
-            let mut validation = Validation::new(Algorithm::RS256);
-            if !config.algorithms.is_empty() {
-                validation.algorithms = config.algorithms;
-            }
-            if !config.required_spec_claims.is_empty() {
-                validation.set_required_spec_claims(&config.required_spec_claims);
-            }
-            if !config.valid_audiences.is_empty() {
-                validation.set_audience(&config.valid_audiences);
+    fn with_load(config: config::JwtDecoder, load: Load) -> Result<Self, JwtDecoderError> {
+        #[allow(unreachable_patterns)]
+        match &config.kind {
+            config::JwtDecoderKind::Tokio(_) => {}
+            other_kind => {
+                return Err(JwtDecoderError::new_failed_precondition(format!(
+                    "Unsupported JwtDecoder kind `{}` for tokio JwtDecoder",
+                    other_kind.as_ref()
+                )))
             }
-            if !config.valid_issuers.is_empty() {
-                validation.set_issuer(&config.valid_issuers);
-            }
-            validation
-        };
-
-        let ttl = config.jwks_ttl.unwrap_or(Duration::from_secs(60));
+        }
 
         Ok(Self {
-            jwks_urls: config.jwks_urls,
-            url_to_jwks: Default::default(),
-            kid_to_jwk: Arc::new(Mutex::new(TimedCache::with_lifespan(ttl.as_secs()))),
-            ttl,
-            max_wait: config.jwks_max_wait.unwrap_or(Duration::from_secs(1)),
-            validation,
+            inner: Arc::new(Inner {
+                settings: DecoderSettings::new(config)?,
+                state: Default::default(),
+                fetches: Default::default(),
+                load,
+            }),
         })
     }
 
-    async fn jwk(&self, kid: &str) -> Result<Option<Arc<DecodingKey>>, JwtDecoderError> {
+    /// Lists the keys currently loaded from the JWKS URLs, refreshing any expired JWKS first.
+    pub async fn keys(&self) -> Vec<JwkInfo> {
+        join_all(self.inner.refresh_expired()).await;
+        self.inner.state.load().keys.clone()
+    }
+
+    /// Reports the state of each JWKS URL as of its last fetch, without refreshing.
+    pub fn status(&self) -> Vec<JwksSourceStatus> {
+        let now = (Instant::now(), SystemTime::now());
+        keys::status(
+            &self.inner.settings,
+            &self.inner.state.load().url_to_jwks,
+            |expiration| keys::system_time(expiration.into_std(), now.0.into_std(), now.1),
+        )
+    }
+
+    pub(crate) async fn jwk(
+        &self,
+        kid: &str,
+    ) -> Result<Option<Arc<VerificationKey>>, JwtDecoderError> {
         // Immediately return an unexpired JWK if available.
-        if let Some(jwk_entry) = self.kid_to_jwk.lock().await.cache_get(kid) {
-            return Ok(Some(jwk_entry.jwk.clone()));
+        if let Some(jwk) = self.inner.state.load().jwk(kid, Instant::now()) {
+            metrics::record_key_cache(true);
+            return Ok(Some(jwk));
+        }
+        metrics::record_key_cache(false);
+
+        // Otherwise, refresh all expired, returning as soon as a fetch yields the JWK.
+        let mut fetches = self
+            .inner
+            .refresh_expired()
+            .into_iter()
+            .collect::<FuturesUnordered<_>>();
+        while fetches.next().await.is_some() {
+            if let Some(jwk) = self.inner.state.load().jwk(kid, Instant::now()) {
+                return Ok(Some(jwk));
+            }
         }
-
-        // Otherwise, refresh all expired.
-        self.refresh_all().await;
 
         // Then, just return whatever is found.
-        Ok(self
-            .kid_to_jwk
-            .lock()
-            .await
-            .cache_get(kid)
-            .map(|e| e.jwk.clone()))
+        let state = self.inner.state.load();
+        if let Some(jwk) = state.jwk(kid, Instant::now()) {
+            return Ok(Some(jwk));
+        }
+        match keys::jwks_unavailable(&self.inner.settings, &state.url_to_jwks) {
+            Some(err) => Err(err),
+            None => Ok(None),
+        }
     }
 
-    async fn fetch_file(path: impl AsRef<Path>) -> Result<JwkSet, JwtDecoderError> {
-        let mut file = File::open(path)
-            .await
-            .map_err(|err| JwtDecoderError::new_jwks_fetch_error(err.to_string()))?;
-        let metadata = file
-            .metadata()
-            .await
-            .map_err(|err| JwtDecoderError::new_jwks_fetch_error(err.to_string()))?;
-        let size = usize::try_from(metadata.size())
-            .map_err(|err| JwtDecoderError::new_jwks_fetch_error(err.to_string()))?;
-        let mut data: Vec<u8> = Vec::with_capacity(size);
-        file.read_to_end(&mut data)
-            .await
-            .map_err(|err| JwtDecoderError::new_jwks_fetch_error(err.to_string()))?;
-        serde_json::from_slice(&data)
-            .map_err(|err| JwtDecoderError::new_jwks_fetch_error(err.to_string()))
+    pub(crate) fn settings(&self) -> &DecoderSettings {
+        &self.inner.settings
     }
 
-    async fn fetch_jwks<'a>(
-        url: &'a Url,
-        max_wait: &Duration,
-        ttl: &Duration,
-    ) -> (&'a Url, JwksResult) {
-        let result = timeout(
-            *max_wait,
-            async move {
-                match url.scheme() {
-                    "file" => match url.to_file_path() {
-                        Ok(path) => Self::fetch_file(path).await,
-                        Err(_) => Err(JwtDecoderError::new_jwks_fetch_error(
-                            "Failed to extract path from file URL".to_owned(),
-                        )),
-                    },
-                    scheme => Err(JwtDecoderError::new_jwks_fetch_error(format!(
-                        "Unsupported JWKS URL scheme `{scheme}`"
-                    ))),
+    /// Creates a decoder for the new config that starts with the JWKS already loaded by this one
+    /// for URLs present in both, so that keys still valid are not refetched.
+    pub fn reconfigured(&self, config: config::JwtDecoder) -> Result<Self, JwtDecoderError> {
+        let decoder = Self::with_load(config, self.inner.load.clone())?;
+        let settings = &decoder.inner.settings;
+        let previous = self.inner.state.load();
+        let now = Instant::now();
+        let max_expiration = now + settings.ttl;
+        let mut url_to_jwks = HashMap::new();
+        for url in &settings.jwks_urls {
+            match previous.url_to_jwks.get(url) {
+                // Screened again since the new config may change which keys are accepted.
+                Some(JwksResult {
+                    jwks: Ok(jwks),
+                    fetched_at,
+                    expiration,
+                }) if *expiration >= now => {
+                    url_to_jwks.insert(
+                        url.to_owned(),
+                        JwksResult {
+                            jwks: Ok(Arc::new(ScreenedJwks::new(
+                                settings,
+                                url,
+                                jwks.jwks.clone(),
+                            ))),
+                            fetched_at: *fetched_at,
+                            expiration: (*expiration).min(max_expiration),
+                        },
+                    );
                 }
+                _ => {}
             }
-            .instrument(info_span!("fetch_jwks", url = url.as_str())),
+        }
+        let state = State::new(settings, url_to_jwks);
+        metrics::record_keys_loaded(&settings.jwks_urls, &state.keys);
+        decoder.inner.state.store(Arc::new(state));
+        Ok(decoder)
+    }
+}
+
+impl Inner {
+    /// Returns the fetches of all expired URLs, starting those not already in flight.
+    fn refresh_expired(self: &Arc<Self>) -> Vec<Fetch> {
+        let now = Instant::now();
+        self.settings
+            .jwks_urls
+            .iter()
+            .filter(|url| self.state.load().is_expired(url, now))
+            .filter_map(|url| match self.fetches.entry(url.to_owned()) {
+                Entry::Occupied(entry) => Some(entry.get().clone()),
+                Entry::Vacant(entry) => {
+                    // A fetch may have completed since the expiration was checked.
+                    if !self.state.load().is_expired(url, now) {
+                        return None;
+                    }
+                    let inner = self.clone();
+                    let url = url.to_owned();
+                    let task = tokio::spawn(async move {
+                        let result = inner.fetch_jwks(&url).await;
+                        inner.publish(&url, result);
+                        inner.fetches.remove(&url);
+                    });
+                    let fetch = task.map(|_| ()).boxed().shared();
+                    Some(entry.insert(fetch).clone())
+                }
+            })
+            .collect()
+    }
+
+    fn publish(&self, url: &Url, result: JwksResult) {
+        // May run more than once under contention, so only merges already screened keys.
+        self.state.rcu(|state| {
+            let mut url_to_jwks = state.url_to_jwks.clone();
+            url_to_jwks.insert(url.to_owned(), result.clone());
+            State::new(&self.settings, url_to_jwks)
+        });
+        metrics::record_keys_loaded(&self.settings.jwks_urls, &self.state.load().keys);
+    }
+
+    async fn fetch_jwks(&self, url: &Url) -> JwksResult {
+        let start = Instant::now();
+        let result = timeout(
+            self.settings.max_wait,
+            (self.load)(url, &self.settings.limits)
+                .instrument(info_span!("fetch_jwks", url = url.as_str())),
         )
         .await
         .unwrap_or_else(|_| {
-            Err(JwtDecoderError::new_jwks_fetch_error(
+            Err(JwtDecoderError::new_jwks_unavailable(
                 "timed out".to_owned(),
             ))
         });
+        metrics::record_jwks_fetch(url, start.elapsed(), &result);
 
-        (
+        JwksResult::new(
+            &self.settings,
             url,
-            JwksResult {
-                jwks: result,
-                expiration: Instant::now() + *ttl,
-            },
+            result,
+            Instant::now() + self.settings.ttl,
         )
     }
-
-    async fn refresh_all(&self) {
-        let mut url_to_jwks = self.url_to_jwks.lock().await;
-        let now = Instant::now();
-
-        let mut fetches = Vec::new();
-        for jwks_url in &self.jwks_urls {
-            if let Some(entry) = url_to_jwks.get(jwks_url) {
-                if entry.expiration < now {
-                    fetches.push(Self::fetch_jwks(jwks_url, &self.max_wait, &self.ttl));
-                }
-            } else {
-                fetches.push(Self::fetch_jwks(jwks_url, &self.max_wait, &self.ttl));
-            }
-        }
-
-        if fetches.is_empty() {
-            return;
-        }
-
-        let results = join_all(fetches).await;
-
-        // Apply results to cache.
-
-        for result in results {
-            let url = result.0;
-            let result = result.1;
-
-            if let Some(existing) = url_to_jwks.get_mut(url) {
-                *existing = result;
-            } else {
-                url_to_jwks.insert(url.to_owned(), result);
-            }
-        }
-
-        // Apply cache in order of URLs with descending priority.
-
-        let mut kid_to_jwk = self.kid_to_jwk.lock().await;
-        kid_to_jwk.cache_clear();
-        for url in &self.jwks_urls {
-            if let Some(result) = url_to_jwks.get(url) {
-                if let Ok(jwks) = &result.jwks {
-                    for key in &jwks.keys {
-                        if let Some(kid) = &key.common.key_id {
-                            // Do not overwrite JWK with lower priority URL JWK
-                            if kid_to_jwk.cache_get(kid).is_some() {
-                                continue;
-                            }
-                            match DecodingKey::from_jwk(key) {
-                                Ok(decoding_key) => {
-                                    kid_to_jwk.cache_set(
-                                        kid.to_owned(),
-                                        JwkEntry {
-                                            jwk: Arc::new(decoding_key),
-                                            expiration: result.expiration,
-                                        },
-                                    );
-                                }
-                                Err(err) => {
-                                    warn!(?url, kid, error = ?err, "Failed to create decoding key from JWK");
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
 }
 
 #[async_trait]
 impl JwtDecode for JwtDecoder {
     async fn decode(&self, token: &str) -> Result<TokenData<serde_json::Value>, JwtDecoderError> {
-        let header = decode_header(token)
-            .map_err(|err| JwtDecoderError::new_header_parsing_failed(err.to_string()))?;
-        let kid = header
-            .kid
-            .as_deref()
-            .ok_or(JwtDecoderError::new_validation_failed(
-                "Header missing kid".to_owned(),
-            ))?;
-        let key = self
-            .jwk(kid)
-            .await?
-            .ok_or(JwtDecoderError::new_missing_key_id())?;
-        let claims: TokenData<serde_json::Value> = decode(token, &key, &self.validation)
-            .map_err(|err| JwtDecoderError::new_validation_failed(err.to_string()))?;
-        Ok(claims)
+        let result = async {
+            let kid = keys::token_kid(token)?;
+            let key = self
+                .jwk(&kid)
+                .await?
+                .ok_or_else(|| JwtDecoderError::new_unknown_kid(kid.to_owned()))?;
+            keys::validate_token(token, &key, &self.inner.settings.validation)
+        }
+        .await;
+        metrics::record_decode(TokenFormat::Jwt, &result);
+        result
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
 
     use base64::engine::{general_purpose::URL_SAFE_NO_PAD, Engine};
+    use futures::{
+        future::{self, join_all},
+        FutureExt,
+    };
     use jsonwebtoken::{
         decode, encode,
         jwk::{
-            AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
-            RSAKeyParameters,
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters,
+            OctetKeyPairType, OctetKeyParameters, OctetKeyType, PublicKeyUse, RSAKeyParameters,
+            RSAKeyType,
         },
         Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
     };
     use rsa::{
         pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey},
+        pkcs8::DecodePrivateKey,
         traits::PublicKeyParts,
         RsaPrivateKey, RsaPublicKey,
     };
-    use tokio::{fs::File, io::AsyncWriteExt};
+    use tokio::{fs::File, io::AsyncWriteExt, time::Instant};
     use tracing_test::traced_test;
     use url::Url;
 
-    use crate::{config, tokio::JwtDecoder, JwtDecode};
+    use crate::{
+        config,
+        error::{JwtDecoderError, JwtDecoderErrorReason},
+        tokio::JwtDecoder,
+        JwtDecode,
+    };
 
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
     struct Claims {
@@ -379,6 +412,8 @@ mod test {
             valid_issuers: vec!["an-issuer".to_owned()],
             jwks_max_wait: None,
             jwks_ttl: None,
+            x5c_trust_anchors_pem: None,
+            jwks_limits: Default::default(),
             kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
         };
 
@@ -390,6 +425,359 @@ mod test {
             Some("user@example.com")
         );
 
-        assert!(jwt_decoder.decode(&bad_aud_jwt).await.is_err());
+        assert_eq!(
+            jwt_decoder
+                .decode(&bad_aud_jwt)
+                .await
+                .err()
+                .map(|e| JwtDecoderErrorReason::from(&e)),
+            Some(JwtDecoderErrorReason::BadAudience)
+        );
+
+        let mut unknown_kid_header = header.clone();
+        unknown_kid_header.kid = Some("other-key".to_owned());
+        let unknown_kid_jwt = encode(
+            &unknown_kid_header,
+            &Claims {
+                aud: aud.to_owned(),
+                sub: "user@example.com".to_owned(),
+                exp: (SystemTime::now() + Duration::from_secs(30))
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            },
+            &encoding_key,
+        )
+        .expect("Failed to encode JWT");
+        assert_eq!(
+            jwt_decoder.decode(&unknown_kid_jwt).await.err(),
+            Some(JwtDecoderError::new_unknown_kid("other-key".to_owned()))
+        );
+    }
+
+    struct TestKey {
+        kid: &'static str,
+        algorithm: Algorithm,
+        encoding_key: EncodingKey,
+        jwk: Jwk,
+    }
+
+    fn test_key(
+        kid: &'static str,
+        algorithm: Algorithm,
+        key_algorithm: Option<KeyAlgorithm>,
+        encoding_key: EncodingKey,
+        parameters: AlgorithmParameters,
+    ) -> TestKey {
+        TestKey {
+            kid,
+            algorithm,
+            encoding_key,
+            jwk: Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    key_algorithm,
+                    key_id: Some(kid.to_owned()),
+                    ..Default::default()
+                },
+                algorithm: parameters,
+            },
+        }
+    }
+
+    fn test_keys() -> Vec<TestKey> {
+        let rsa_pem = include_str!("testing/rs256.pem");
+        let rsa_key = RsaPrivateKey::from_pkcs8_pem(rsa_pem).unwrap();
+        let rsa = || {
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(rsa_key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(rsa_key.e().to_bytes_be()),
+            })
+        };
+        let rsa_encoding_key = || EncodingKey::from_rsa_pem(rsa_pem.as_bytes()).unwrap();
+
+        let ec = |algorithm: &'static rcgen::SignatureAlgorithm, curve: EllipticCurve| {
+            let key = rcgen::KeyPair::generate_for(algorithm).unwrap();
+            // Uncompressed SEC1 point: 0x04 || x || y
+            let point = key.public_key_raw();
+            let half = (point.len() - 1) / 2;
+            (
+                EncodingKey::from_ec_der(&key.serialize_der()),
+                AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve,
+                    x: URL_SAFE_NO_PAD.encode(&point[1..1 + half]),
+                    y: URL_SAFE_NO_PAD.encode(&point[1 + half..]),
+                }),
+            )
+        };
+        let (es256_key, es256) = ec(&rcgen::PKCS_ECDSA_P256_SHA256, EllipticCurve::P256);
+        let (es384_key, es384) = ec(&rcgen::PKCS_ECDSA_P384_SHA384, EllipticCurve::P384);
+
+        let ed_key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ED25519).unwrap();
+        let ed_dsa = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(ed_key.public_key_raw()),
+        });
+
+        let secret = b"an internal shared secret of 32+ bytes";
+        let oct = AlgorithmParameters::OctetKey(OctetKeyParameters {
+            key_type: OctetKeyType::Octet,
+            value: URL_SAFE_NO_PAD.encode(secret),
+        });
+
+        vec![
+            test_key(
+                "rs256",
+                Algorithm::RS256,
+                Some(KeyAlgorithm::RS256),
+                rsa_encoding_key(),
+                rsa(),
+            ),
+            test_key(
+                "ps256",
+                Algorithm::PS256,
+                Some(KeyAlgorithm::PS256),
+                rsa_encoding_key(),
+                rsa(),
+            ),
+            // Without a declared `alg`, a key may be used with any algorithm of its family.
+            test_key("rsa", Algorithm::RS384, None, rsa_encoding_key(), rsa()),
+            test_key(
+                "es256",
+                Algorithm::ES256,
+                Some(KeyAlgorithm::ES256),
+                es256_key,
+                es256,
+            ),
+            test_key(
+                "es384",
+                Algorithm::ES384,
+                Some(KeyAlgorithm::ES384),
+                es384_key,
+                es384,
+            ),
+            test_key(
+                "ed-dsa",
+                Algorithm::EdDSA,
+                Some(KeyAlgorithm::EdDSA),
+                EncodingKey::from_ed_der(&ed_key.serialize_der()),
+                ed_dsa,
+            ),
+            test_key(
+                "hs256",
+                Algorithm::HS256,
+                Some(KeyAlgorithm::HS256),
+                EncodingKey::from_secret(secret),
+                oct,
+            ),
+        ]
+    }
+
+    fn sign(kid: &str, algorithm: Algorithm, encoding_key: &EncodingKey) -> String {
+        let mut header = Header::new(algorithm);
+        header.kid = Some(kid.to_owned());
+        encode(
+            &header,
+            &Claims {
+                aud: "some-users".to_owned(),
+                sub: "user@example.com".to_owned(),
+                exp: u64::MAX / 2,
+            },
+            encoding_key,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn algorithm_matrix() {
+        let keys = test_keys();
+        let mut jwks = keys.iter().map(|key| key.jwk.clone()).collect::<Vec<_>>();
+        let mut encryption_jwk = keys[0].jwk.clone();
+        encryption_jwk.common.key_id = Some("rsa-enc".to_owned());
+        encryption_jwk.common.public_key_use = Some(PublicKeyUse::Encryption);
+        jwks.push(encryption_jwk);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("keys.jwks");
+        std::fs::write(
+            &temp_file,
+            serde_json::to_vec(&JwkSet { keys: jwks }).unwrap(),
+        )
+        .unwrap();
+
+        let jwt_decoder = |algorithms: Vec<Algorithm>| {
+            JwtDecoder::new(config::JwtDecoder {
+                jwks_urls: vec![Url::from_file_path(&temp_file).unwrap()],
+                algorithms,
+                required_spec_claims: vec!["sub".to_owned()],
+                valid_audiences: vec!["some-users".to_owned()],
+                valid_issuers: vec![],
+                jwks_max_wait: None,
+                jwks_ttl: None,
+                x5c_trust_anchors_pem: None,
+                jwks_limits: Default::default(),
+                kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+            })
+            .unwrap()
+        };
+
+        let all = jwt_decoder(keys.iter().map(|key| key.algorithm).collect());
+        for key in &keys {
+            let token = sign(key.kid, key.algorithm, &key.encoding_key);
+            assert!(
+                all.decode(&token).await.is_ok(),
+                "{} {:?}",
+                key.kid,
+                key.algorithm
+            );
+        }
+
+        // Only the configured algorithms are accepted, defaulting to RS256.
+        let asymmetric = jwt_decoder(vec![Algorithm::RS256, Algorithm::ES256, Algorithm::EdDSA]);
+        let default = jwt_decoder(vec![]);
+        for (jwt_decoder, key, accepted) in [
+            (&asymmetric, &keys[0], true),
+            (&asymmetric, &keys[3], true),
+            (&asymmetric, &keys[4], false),
+            (&asymmetric, &keys[6], false),
+            (&default, &keys[0], true),
+            (&default, &keys[3], false),
+        ] {
+            let result = jwt_decoder
+                .decode(&sign(key.kid, key.algorithm, &key.encoding_key))
+                .await;
+            match accepted {
+                true => assert!(result.is_ok(), "{}", key.kid),
+                false => assert_eq!(
+                    result.err(),
+                    Some(JwtDecoderError::new_invalid_algorithm()),
+                    "{}",
+                    key.kid
+                ),
+            }
+        }
+
+        let (rs256, ps256, rsa, hs256) = (&keys[0], &keys[1], &keys[2], &keys[6]);
+        for token in [
+            // A key declaring its `alg` is not used with another algorithm of its family.
+            sign(ps256.kid, Algorithm::RS256, &ps256.encoding_key),
+            // Nor is a key used with an algorithm of another family, e.g. an RSA public key as
+            // an HMAC secret.
+            sign(rsa.kid, Algorithm::HS256, &hs256.encoding_key),
+            sign(rs256.kid, Algorithm::HS256, &hs256.encoding_key),
+        ] {
+            assert_eq!(
+                all.decode(&token).await.err(),
+                Some(JwtDecoderError::new_invalid_algorithm())
+            );
+        }
+
+        assert_eq!(
+            all.decode(&sign("rsa-enc", Algorithm::RS256, &rs256.encoding_key))
+                .await
+                .err(),
+            Some(JwtDecoderError::new_unknown_kid("rsa-enc".to_owned()))
+        );
+    }
+
+    /// Decodes stay fast while a higher priority JWKS URL is timing out, both for the decodes
+    /// that first load the keys and for those made while a miss waits on the slow URL.
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn decode_latency_while_jwks_times_out() {
+        let max_wait = Duration::from_secs(2);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let encoding_key = EncodingKey::from_ec_der(&key.serialize_der());
+        let point = key.public_key_raw();
+        let jwk = Jwk {
+            common: CommonParameters {
+                key_id: Some("fast".to_owned()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }),
+        };
+        let token = |kid: &str| {
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = Some(kid.to_owned());
+            encode(
+                &header,
+                &serde_json::json!({ "sub": "user@example.com", "exp": u64::MAX / 2 }),
+                &encoding_key,
+            )
+            .unwrap()
+        };
+
+        let fast = Url::parse("file:///fast.jwks").unwrap();
+        let slow = Url::parse("file:///slow.jwks").unwrap();
+        let jwks = JwkSet { keys: vec![jwk] };
+        let load: super::Load = {
+            let fast = fast.clone();
+            Arc::new(move |url, _| {
+                if *url == fast {
+                    future::ready(Ok(jwks.clone())).boxed()
+                } else {
+                    future::pending().boxed()
+                }
+            })
+        };
+
+        let jwt_decoder = Arc::new(
+            JwtDecoder::with_load(
+                config::JwtDecoder {
+                    jwks_urls: vec![slow, fast],
+                    algorithms: vec![Algorithm::ES256],
+                    required_spec_claims: vec!["sub".to_owned()],
+                    valid_audiences: vec![],
+                    valid_issuers: vec![],
+                    jwks_max_wait: Some(max_wait),
+                    jwks_ttl: None,
+                    x5c_trust_anchors_pem: None,
+                    jwks_limits: Default::default(),
+                    kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+                },
+                load,
+            )
+            .unwrap(),
+        );
+        let timed_decode = |token: String| {
+            let jwt_decoder = jwt_decoder.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let result = jwt_decoder.decode(&token).await;
+                (result, start.elapsed())
+            })
+        };
+
+        // Concurrent cold misses share one fetch per URL and return once the fast URL loads.
+        let fast_token = token("fast");
+        let cold = join_all((0..100).map(|_| timed_decode(fast_token.clone()))).await;
+        for decode in cold {
+            let (result, elapsed) = decode.unwrap();
+            assert!(result.is_ok());
+            assert_eq!(elapsed, Duration::ZERO);
+        }
+
+        // A miss waits on the slow URL without delaying decodes of loaded keys.
+        let unknown = timed_decode(token("unknown"));
+        for decode in join_all((0..1000).map(|_| timed_decode(fast_token.clone()))).await {
+            let (result, elapsed) = decode.unwrap();
+            assert!(result.is_ok());
+            assert_eq!(elapsed, Duration::ZERO);
+        }
+        assert!(!unknown.is_finished());
+        let (result, elapsed) = unknown.await.unwrap();
+        assert_eq!(
+            result.err(),
+            Some(JwtDecoderError::new_unknown_kid("unknown".to_owned()))
+        );
+        assert_eq!(elapsed, max_wait);
+        assert!(jwt_decoder.status()[0].error.is_some());
     }
 }
@@ -0,0 +1,321 @@
+//! Validation of a JWK's `x5c` certificate chain against configured trust anchors.
+
+use base64::engine::{
+    general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::jwk::{AlgorithmParameters, Jwk};
+use x509_parser::{
+    certificate::X509Certificate, pem::Pem, prelude::FromDer, public_key::PublicKey,
+};
+
+use crate::error::JwtDecoderError;
+
+/// DER encoded certificates trusted as roots of JWK `x5c` chains.
+#[derive(Clone, Debug)]
+pub struct TrustAnchors {
+    certificates: Vec<Vec<u8>>,
+}
+
+impl TrustAnchors {
+    pub fn from_pem(pem: &str) -> Result<Self, JwtDecoderError> {
+        let mut certificates = Vec::new();
+        for pem in Pem::iter_from_buffer(pem.as_bytes()) {
+            let pem = pem.map_err(|err| {
+                JwtDecoderError::new_failed_precondition(format!(
+                    "Failed to parse x5c trust anchor PEM: {err}"
+                ))
+            })?;
+            X509Certificate::from_der(&pem.contents).map_err(|err| {
+                JwtDecoderError::new_failed_precondition(format!(
+                    "Failed to parse x5c trust anchor certificate: {err}"
+                ))
+            })?;
+            certificates.push(pem.contents);
+        }
+        if certificates.is_empty() {
+            return Err(JwtDecoderError::new_failed_precondition(
+                "No certificates found in x5c trust anchor PEM".to_owned(),
+            ));
+        }
+        Ok(Self { certificates })
+    }
+
+    /// Validates that the JWK's `x5c` chain is currently valid, chains up to one of the trust
+    /// anchors, and that its leaf certificate carries the same public key as the JWK.
+    pub fn validate(&self, jwk: &Jwk) -> Result<(), JwtDecoderError> {
+        let kid = jwk.common.key_id.as_deref().unwrap_or_default();
+        let untrusted =
+            |message: String| JwtDecoderError::new_untrusted_jwk(kid.to_owned(), message);
+
+        let chain = match jwk.common.x509_chain.as_deref() {
+            Some(chain) if !chain.is_empty() => chain,
+            _ => return Err(untrusted("JWK has no `x5c` certificate chain".to_owned())),
+        };
+        let chain = chain
+            .iter()
+            .map(|certificate| STANDARD.decode(certificate))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| untrusted(format!("Invalid `x5c` base64: {err}")))?;
+        let chain = chain
+            .iter()
+            .map(|der| X509Certificate::from_der(der).map(|(_, certificate)| certificate))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| untrusted(format!("Invalid `x5c` certificate: {err}")))?;
+
+        for certificate in &chain {
+            if !certificate.validity().is_valid() {
+                return Err(untrusted(format!(
+                    "Certificate `{}` is not valid at the current time",
+                    certificate.subject()
+                )));
+            }
+        }
+
+        for pair in chain.windows(2) {
+            let (certificate, issuer) = (&pair[0], &pair[1]);
+            if !issued_by(certificate, issuer) {
+                return Err(untrusted(format!(
+                    "Certificate `{}` is not issued by `{}`",
+                    certificate.subject(),
+                    issuer.subject()
+                )));
+            }
+        }
+
+        // The chain may or may not include the trust anchor itself.
+        let last = chain.last().expect("chain is not empty");
+        let anchored = self.certificates.iter().any(|der| {
+            let Ok((_, anchor)) = X509Certificate::from_der(der) else {
+                return false;
+            };
+            anchor.as_ref() == last.as_ref()
+                || (anchor.validity().is_valid() && issued_by(last, &anchor))
+        });
+        if !anchored {
+            return Err(untrusted(format!(
+                "Certificate `{}` does not chain to a trust anchor",
+                last.subject()
+            )));
+        }
+
+        if !public_key_matches(jwk, &chain[0])? {
+            return Err(untrusted(
+                "Leaf certificate public key does not match JWK".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn issued_by(certificate: &X509Certificate, issuer: &X509Certificate) -> bool {
+    certificate.issuer() == issuer.subject()
+        && issuer.is_ca()
+        && certificate
+            .verify_signature(Some(issuer.public_key()))
+            .is_ok()
+}
+
+fn public_key_matches(jwk: &Jwk, leaf: &X509Certificate) -> Result<bool, JwtDecoderError> {
+    let kid = jwk.common.key_id.as_deref().unwrap_or_default();
+    let decode = |value: &str| {
+        URL_SAFE_NO_PAD.decode(value).map_err(|err| {
+            JwtDecoderError::new_unsupported_jwk(kid.to_owned(), format!("Invalid base64: {err}"))
+        })
+    };
+    let public_key = leaf.public_key();
+
+    Ok(match &jwk.algorithm {
+        AlgorithmParameters::RSA(params) => match public_key.parsed() {
+            Ok(PublicKey::RSA(rsa)) => {
+                trim_leading_zeros(rsa.modulus) == trim_leading_zeros(&decode(&params.n)?)
+                    && trim_leading_zeros(rsa.exponent) == trim_leading_zeros(&decode(&params.e)?)
+            }
+            _ => false,
+        },
+        AlgorithmParameters::EllipticCurve(params) => {
+            // Uncompressed SEC1 point: 0x04 || x || y
+            let mut point = vec![0x04];
+            point.extend(decode(&params.x)?);
+            point.extend(decode(&params.y)?);
+            public_key.subject_public_key.data.as_ref() == point.as_slice()
+        }
+        AlgorithmParameters::OctetKeyPair(params) => {
+            public_key.subject_public_key.data.as_ref() == decode(&params.x)?.as_slice()
+        }
+        AlgorithmParameters::OctetKey(_) => false,
+    })
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[cfg(test)]
+mod test {
+    use base64::engine::{
+        general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+        Engine,
+    };
+    use jsonwebtoken::{
+        encode,
+        jwk::{
+            AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+            EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+        },
+        Algorithm, EncodingKey, Header,
+    };
+    use rcgen::{
+        date_time_ymd, BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair,
+    };
+    use serde_json::json;
+    use url::Url;
+
+    use crate::{config, error::JwtDecoderError, tokio::JwtDecoder, JwtDecode};
+
+    use super::TrustAnchors;
+
+    fn ca(name: &str) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        (params.self_signed(&key).unwrap(), key)
+    }
+
+    fn leaf(issuer: &Certificate, issuer_key: &KeyPair, expired: bool) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec!["issuer.example.com".to_owned()]).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "issuer.example.com");
+        if expired {
+            params.not_before = date_time_ymd(2000, 1, 1);
+            params.not_after = date_time_ymd(2001, 1, 1);
+        }
+        (params.signed_by(&key, issuer, issuer_key).unwrap(), key)
+    }
+
+    fn jwk(key: &KeyPair, chain: &[&Certificate]) -> Jwk {
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let point = key.public_key_raw();
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::ES256),
+                key_id: Some("my-key".to_owned()),
+                x509_chain: Some(
+                    chain
+                        .iter()
+                        .map(|certificate| STANDARD.encode(certificate.der()))
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }),
+        }
+    }
+
+    #[test]
+    fn x5c_chain_validation() {
+        let (root, root_key) = ca("Test Root CA");
+        let (other_root, other_root_key) = ca("Other Root CA");
+        let trust_anchors = TrustAnchors::from_pem(&root.pem()).unwrap();
+
+        let (valid, valid_key) = leaf(&root, &root_key, false);
+        assert!(trust_anchors.validate(&jwk(&valid_key, &[&valid])).is_ok());
+        assert!(trust_anchors
+            .validate(&jwk(&valid_key, &[&valid, &root]))
+            .is_ok());
+
+        let (expired, expired_key) = leaf(&root, &root_key, true);
+        let (untrusted, untrusted_key) = leaf(&other_root, &other_root_key, false);
+        let (_, mismatched_key) = leaf(&root, &root_key, false);
+
+        for jwk in [
+            jwk(&expired_key, &[&expired]),
+            jwk(&untrusted_key, &[&untrusted, &other_root]),
+            jwk(&mismatched_key, &[&valid]),
+            jwk(&valid_key, &[]),
+        ] {
+            assert!(matches!(
+                trust_anchors.validate(&jwk),
+                Err(JwtDecoderError::UntrustedJwk { .. })
+            ));
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn decoder_rejects_untrusted_jwks() {
+        let (root, root_key) = ca("Test Root CA");
+        let (other_root, other_root_key) = ca("Other Root CA");
+        let (trusted, trusted_key) = leaf(&root, &root_key, false);
+        let (untrusted, untrusted_key) = leaf(&other_root, &other_root_key, false);
+
+        let mut trusted_jwk = jwk(&trusted_key, &[&trusted]);
+        trusted_jwk.common.key_id = Some("trusted".to_owned());
+        let mut untrusted_jwk = jwk(&untrusted_key, &[&untrusted, &other_root]);
+        untrusted_jwk.common.key_id = Some("untrusted".to_owned());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("keys.jwks");
+        std::fs::write(
+            &temp_file,
+            serde_json::to_vec(&JwkSet {
+                keys: vec![trusted_jwk, untrusted_jwk],
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let jwt_decoder = JwtDecoder::new(config::JwtDecoder {
+            jwks_urls: vec![Url::from_file_path(&temp_file).unwrap()],
+            algorithms: vec![Algorithm::ES256],
+            required_spec_claims: vec!["sub".to_owned()],
+            valid_audiences: vec![],
+            valid_issuers: vec![],
+            jwks_max_wait: None,
+            jwks_ttl: None,
+            x5c_trust_anchors_pem: Some(root.pem()),
+            jwks_limits: Default::default(),
+            kind: config::JwtDecoderKind::Tokio(config::TokioJwtDecoder {}),
+        })
+        .unwrap();
+
+        let token = |kid: &str, key: &KeyPair| {
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = Some(kid.to_owned());
+            encode(
+                &header,
+                &json!({ "sub": "user@example.com", "exp": u64::MAX / 2 }),
+                &EncodingKey::from_ec_der(&key.serialize_der()),
+            )
+            .unwrap()
+        };
+
+        assert!(jwt_decoder
+            .decode(&token("trusted", &trusted_key))
+            .await
+            .is_ok());
+        assert!(jwt_decoder
+            .decode(&token("untrusted", &untrusted_key))
+            .await
+            .is_err());
+        assert_eq!(
+            jwt_decoder
+                .keys()
+                .await
+                .into_iter()
+                .map(|key| (key.kid, key.key_type))
+                .collect::<Vec<_>>(),
+            vec![("trusted".to_owned(), "EC".to_owned())]
+        );
+    }
+}
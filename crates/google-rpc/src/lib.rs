@@ -0,0 +1,2 @@
+#[cfg(feature = "prost-serde")]
+pub mod prost_serde;
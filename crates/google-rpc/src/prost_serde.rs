@@ -0,0 +1 @@
+include!(concat!(env!("OUT_DIR"), "/appbiotic_api_prost_serde_build/_index.rs"));
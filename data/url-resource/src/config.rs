@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use url::Url;
 
-// NOTE: Could add retry logic before caching a failure result.
+use crate::error::UrlResourceErrorReason;
 
 #[derive(Debug)]
 #[cfg_attr(
@@ -24,17 +24,211 @@ pub struct UrlResource {
     )]
     pub cache_ttl: Option<Duration>,
 
-    #[cfg(feature = "sha256")]
+    /// How long a failed fetch is cached, defaulting to [UrlResource::cache_ttl].
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub error_cache_ttl: Option<Duration>,
+
+    /// How long after expiring the previous content is served, flagged as stale, while refetches
+    /// fail. Failures are returned immediately if unset.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub serve_stale_on_error: Option<Duration>,
+
+    /// Digests to compute while reading the content. Accepts a single algorithm or a list.
+    #[cfg(feature = "hash")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Vec::is_empty",
+            with = "serde_with::As::<serde_with::OneOrMany<serde_with::Same>>"
+        )
+    )]
+    pub hash: Vec<UrlResourceHash>,
+
+    /// Whitespace separated digests, each either `<algorithm>:<hex>` or Subresource Integrity
+    /// `<algorithm>-<base64>`. As with an SRI `integrity` value, only those of the strongest
+    /// algorithm are checked and the content must match one of them, so that several can be
+    /// listed while rotating. Otherwise the fetch fails with
+    /// [crate::error::UrlResourceError::IntegrityMismatch].
+    #[cfg(feature = "hash")]
     #[cfg_attr(
         feature = "serde",
         serde(default, skip_serializing_if = "Option::is_none")
     )]
-    pub hash: Option<UrlResourceHash>,
+    pub expected_hash: Option<String>,
+
+    /// Refetches the resource each time the cached result expires instead of waiting for the
+    /// next fetch, defaulting to false.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub background_refresh: Option<bool>,
+
+    /// Invalidates the cache as soon as the file of a `file` URL changes, refetching right away
+    /// with [UrlResource::background_refresh]. Defaults to false and requires the `notify`
+    /// feature.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub watch: Option<bool>,
+
+    /// Bytes above which [crate::UrlResourceFetch::fetch] fails rather than buffering the
+    /// content. Unlimited if unset.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_size: Option<u64>,
+
+    /// Retries failed fetches before caching the failure. Failures are not retried if unset.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub retry: Option<RetryOptions>,
+
+    /// Options for `http` and `https` URLs.
+    #[cfg(feature = "http")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub http: Option<HttpOptions>,
 
     #[cfg_attr(feature = "serde", serde(flatten))]
     pub provider: UrlResourceProvider,
 }
 
+impl UrlResource {
+    /// A config for the URL with every option left to its default.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            cache_ttl: None,
+            error_cache_ttl: None,
+            serve_stale_on_error: None,
+            #[cfg(feature = "hash")]
+            hash: Vec::new(),
+            #[cfg(feature = "hash")]
+            expected_hash: None,
+            background_refresh: None,
+            watch: None,
+            max_size: None,
+            retry: None,
+            #[cfg(feature = "http")]
+            http: None,
+            provider: Default::default(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct HttpOptions {
+    /// Defaults to 10 seconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub connect_timeout: Option<Duration>,
+
+    /// Limits the whole request including reading the body, defaulting to 30 seconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub timeout: Option<Duration>,
+
+    /// Redirects followed before failing, defaulting to 10. Zero disables following redirects.
+    /// Redirects from `https` to `http` are never followed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_redirects: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct RetryOptions {
+    /// Total fetch attempts including the first, defaulting to 3.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_attempts: Option<u32>,
+
+    /// Delay before the first retry, defaulting to 100 milliseconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub initial_backoff: Option<Duration>,
+
+    /// Upper bound on the delay between attempts, defaulting to 10 seconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            deserialize_with = "duration_str::deserialize_option_duration"
+        )
+    )]
+    pub max_backoff: Option<Duration>,
+
+    /// Factor applied to the delay after each retry, defaulting to 2.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub multiplier: Option<f64>,
+
+    /// Fraction of each delay, from 0 to 1, that is randomly subtracted, defaulting to 0.2.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub jitter: Option<f64>,
+
+    /// Failures that are retried, defaulting to `SERVICE_UNAVAILABLE` and `RESOURCE_READ_ERROR`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub retryable: Option<Vec<UrlResourceErrorReason>>,
+}
+
 #[derive(Debug, strum_macros::EnumDiscriminants)]
 #[strum_discriminants(derive(strum::AsRefStr, strum::Display))]
 #[strum_discriminants(name(UrlResourceProviderKind))]
@@ -49,18 +243,35 @@ pub enum UrlResourceProvider {
     Tokio(TokioUrlResourceProvider),
 }
 
-#[cfg(feature = "sha256")]
-#[derive(Clone, Debug)]
+impl Default for UrlResourceProvider {
+    fn default() -> Self {
+        Self::Tokio(Default::default())
+    }
+}
+
+/// In order of increasing strength.
+#[cfg(feature = "hash")]
+#[derive(
+    Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, strum::AsRefStr, strum::EnumString,
+)]
+#[strum(serialize_all = "lowercase")]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
     serde(rename_all = "UPPERCASE")
 )]
 pub enum UrlResourceHash {
+    #[cfg(feature = "sha256")]
     Sha256,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    #[cfg(feature = "sha384")]
+    Sha384,
+    #[cfg(feature = "sha512")]
+    Sha512,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
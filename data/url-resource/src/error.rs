@@ -6,15 +6,26 @@ use crate::config::UrlResourceProviderKind;
 #[strum_discriminants(derive(strum::AsRefStr))]
 #[strum_discriminants(name(UrlResourceErrorReason))]
 #[strum_discriminants(strum(serialize_all = "SCREAMING_SNAKE_CASE"))]
+#[cfg_attr(
+    feature = "serde",
+    strum_discriminants(
+        derive(serde::Deserialize, serde::Serialize),
+        serde(rename_all = "SCREAMING_SNAKE_CASE")
+    )
+)]
 pub enum UrlResourceError {
     #[error("Failed precondition: {message}")]
     FailedPrecondition { message: String },
+    #[error("Resource integrity check failed: expected `{expected}` but was `{actual}`")]
+    IntegrityMismatch { expected: String, actual: String },
     #[error("Denied access to resource: {message}")]
     ResourceAccessDenied { message: String },
     #[error("Resource not found: {message}")]
     ResourceNotFound { message: String },
     #[error("Error resource: {message}")]
     ResourceReadError { message: String },
+    #[error("Resource larger than the max size of {max_size} bytes")]
+    ResourceTooLarge { max_size: u64 },
     #[error("Service unavailable: {message}")]
     ServiceUnavailable { message: String },
     #[error("Unknown: {message}")]
@@ -0,0 +1,265 @@
+//! Content digests in `<algorithm>:<hex>` and Subresource Integrity `<algorithm>-<base64>` forms.
+
+use std::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{config::UrlResourceHash, error::UrlResourceError};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Digest {
+    pub algorithm: UrlResourceHash,
+    pub value: Vec<u8>,
+}
+
+impl Digest {
+    /// Formats as a Subresource Integrity hash expression such as `sha384-<base64>`.
+    pub fn sri(&self) -> String {
+        format!(
+            "{}-{}",
+            self.algorithm.as_ref(),
+            STANDARD.encode(&self.value)
+        )
+    }
+}
+
+/// Formats as `<algorithm>:<hex>`, such as `sha256:d9014c46...`.
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.algorithm.as_ref(),
+            hex::encode(&self.value)
+        )
+    }
+}
+
+/// Parses either `<algorithm>:<hex>` or an SRI `<algorithm>-<base64>` hash expression, ignoring
+/// any SRI `?` options.
+impl FromStr for Digest {
+    type Err = UrlResourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            UrlResourceError::new_failed_precondition(format!("Invalid digest `{s}`: {reason}"))
+        };
+        let (algorithm, value) = match (s.split_once(':'), s.split_once('-')) {
+            (Some((algorithm, hex)), _) => (
+                algorithm,
+                hex::decode(hex).map_err(|err| invalid(&err.to_string()))?,
+            ),
+            (None, Some((algorithm, base64))) => {
+                let base64 = base64.split_once('?').map_or(base64, |(base64, _)| base64);
+                (
+                    algorithm,
+                    STANDARD
+                        .decode(base64)
+                        .map_err(|err| invalid(&err.to_string()))?,
+                )
+            }
+            (None, None) => return Err(invalid("expected `<algorithm>:<hex>` or SRI format")),
+        };
+        let algorithm = UrlResourceHash::from_str(algorithm)
+            .map_err(|_| invalid(&format!("unsupported algorithm `{algorithm}`")))?;
+        if value.len() != algorithm.output_len() {
+            return Err(invalid(&format!(
+                "expected {} bytes but was {}",
+                algorithm.output_len(),
+                value.len()
+            )));
+        }
+        Ok(Self { algorithm, value })
+    }
+}
+
+/// Parses whitespace separated digests, such as an SRI `integrity` attribute value.
+pub fn parse_integrity(value: &str) -> Result<Vec<Digest>, UrlResourceError> {
+    value.split_whitespace().map(Digest::from_str).collect()
+}
+
+/// Keeps only the digests of the strongest algorithm, the alternatives that Subresource Integrity
+/// checks content against.
+pub fn strongest(mut digests: Vec<Digest>) -> Vec<Digest> {
+    if let Some(algorithm) = digests.iter().map(|digest| digest.algorithm).max() {
+        digests.retain(|digest| digest.algorithm == algorithm);
+    }
+    digests
+}
+
+/// Formats digests as an SRI `integrity` attribute value.
+pub fn integrity(digests: &[Digest]) -> String {
+    digests
+        .iter()
+        .map(Digest::sri)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl UrlResourceHash {
+    /// Digest length in bytes.
+    pub fn output_len(self) -> usize {
+        match self {
+            #[cfg(feature = "sha256")]
+            Self::Sha256 => 32,
+            #[cfg(feature = "sha384")]
+            Self::Sha384 => 48,
+            #[cfg(feature = "sha512")]
+            Self::Sha512 => 64,
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => blake3::OUT_LEN,
+        }
+    }
+}
+
+/// Computes digests incrementally as content is read.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+pub(crate) struct Hasher {
+    states: Vec<HasherState>,
+}
+
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+enum HasherState {
+    #[cfg(feature = "sha256")]
+    Sha256(sha2::Sha256),
+    #[cfg(feature = "sha384")]
+    Sha384(sha2::Sha384),
+    #[cfg(feature = "sha512")]
+    Sha512(sha2::Sha512),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+impl Hasher {
+    pub(crate) fn new(algorithms: impl IntoIterator<Item = UrlResourceHash>) -> Self {
+        let mut seen = Vec::new();
+        let states = algorithms
+            .into_iter()
+            .filter(|algorithm| {
+                let new = !seen.contains(algorithm);
+                seen.push(*algorithm);
+                new
+            })
+            .map(|algorithm| match algorithm {
+                #[cfg(feature = "sha256")]
+                UrlResourceHash::Sha256 => HasherState::Sha256(Default::default()),
+                #[cfg(feature = "sha384")]
+                UrlResourceHash::Sha384 => HasherState::Sha384(Default::default()),
+                #[cfg(feature = "sha512")]
+                UrlResourceHash::Sha512 => HasherState::Sha512(Default::default()),
+                #[cfg(feature = "blake3")]
+                UrlResourceHash::Blake3 => HasherState::Blake3(Default::default()),
+            })
+            .collect();
+        Self { states }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        #[cfg(any(feature = "sha256", feature = "sha384", feature = "sha512"))]
+        use sha2::Digest as _;
+
+        for state in &mut self.states {
+            match state {
+                #[cfg(feature = "sha256")]
+                HasherState::Sha256(hasher) => hasher.update(data),
+                #[cfg(feature = "sha384")]
+                HasherState::Sha384(hasher) => hasher.update(data),
+                #[cfg(feature = "sha512")]
+                HasherState::Sha512(hasher) => hasher.update(data),
+                #[cfg(feature = "blake3")]
+                HasherState::Blake3(hasher) => {
+                    hasher.update(data);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<Digest> {
+        #[cfg(any(feature = "sha256", feature = "sha384", feature = "sha512"))]
+        use sha2::Digest as _;
+
+        self.states
+            .into_iter()
+            .map(|state| match state {
+                #[cfg(feature = "sha256")]
+                HasherState::Sha256(hasher) => Digest {
+                    algorithm: UrlResourceHash::Sha256,
+                    value: hasher.finalize().to_vec(),
+                },
+                #[cfg(feature = "sha384")]
+                HasherState::Sha384(hasher) => Digest {
+                    algorithm: UrlResourceHash::Sha384,
+                    value: hasher.finalize().to_vec(),
+                },
+                #[cfg(feature = "sha512")]
+                HasherState::Sha512(hasher) => Digest {
+                    algorithm: UrlResourceHash::Sha512,
+                    value: hasher.finalize().to_vec(),
+                },
+                #[cfg(feature = "blake3")]
+                HasherState::Blake3(hasher) => Digest {
+                    algorithm: UrlResourceHash::Blake3,
+                    value: hasher.finalize().as_bytes().to_vec(),
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "sha256",
+    feature = "sha384",
+    feature = "sha512",
+    feature = "blake3"
+))]
+mod test {
+    use std::str::FromStr;
+
+    use crate::config::UrlResourceHash;
+
+    use super::{integrity, parse_integrity, strongest, Digest, Hasher};
+
+    #[test]
+    fn digests() {
+        let mut hasher = Hasher::new([
+            UrlResourceHash::Sha256,
+            UrlResourceHash::Sha384,
+            UrlResourceHash::Sha512,
+            UrlResourceHash::Blake3,
+            UrlResourceHash::Sha256,
+        ]);
+        hasher.update(b"Hello, ");
+        hasher.update(b"world!\n");
+        let digests = hasher.finalize();
+
+        assert_eq!(
+            digests.iter().map(Digest::to_string).collect::<Vec<_>>(),
+            [
+                "sha256:d9014c4624844aa5bac314773d6b689ad467fa4e1d1a50a1b8a99d5a95f72ff5",
+                "sha384:79a7aec70847c242102b889b298a0720803b340b350cd9e89f574c684d46bfb232b92d2df356fd77e4d2047c43b3f8a0",
+                "sha512:09e1e2a84c92b56c8280f4a1203c7cffd61b162cfe987278d4d6be9afbf38c0e8934cdadf83751f4e99d111352bffefc958e5a4852c8a7a29c95742ce59288a8",
+                "blake3:94f1675bac4f8bc3c593c63dbf5fe78a0bfda01082af85d5b41a65096db56bff",
+            ]
+        );
+
+        let sri = integrity(&digests);
+        assert_eq!(parse_integrity(&sri).unwrap(), digests);
+        assert_eq!(
+            strongest(parse_integrity(&sri).unwrap()),
+            [digests[2].clone()]
+        );
+        for digest in &digests {
+            assert_eq!(Digest::from_str(&digest.to_string()).as_ref(), Ok(digest));
+        }
+        assert_eq!(
+            Digest::from_str("sha256-2QFMRiSESqW6wxR3PWtomtRn+k4dGlChuKmdWpX3L/U=?ct=text/plain"),
+            Ok(digests[0].clone())
+        );
+
+        for invalid in ["md5:abcd", "sha256:d9014c", "sha256-%%%", "d9014c"] {
+            assert!(Digest::from_str(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -4,20 +4,77 @@ use async_trait::async_trait;
 
 pub mod config;
 pub mod error;
+#[cfg(feature = "hash")]
+pub mod hash;
 #[cfg(feature = "tokio")]
 pub mod tokio;
 
+#[cfg(all(
+    feature = "hash",
+    not(any(
+        feature = "sha256",
+        feature = "sha384",
+        feature = "sha512",
+        feature = "blake3"
+    ))
+))]
+compile_error!(
+    "The `hash` feature is internal, enable it with one of `sha256`, `sha384`, `sha512` or `blake3`"
+);
+
+use std::{pin::Pin, time::SystemTime};
+
 use bytes::Bytes;
 use error::UrlResourceError;
+use tokio_stream::Stream;
+use url::Url;
+
+pub type UrlResourceStream = Pin<Box<dyn Stream<Item = Result<Bytes, UrlResourceError>> + Send>>;
 
-/// NOTE: Could add a streaming method.
 #[async_trait]
 pub trait UrlResourceFetch {
     async fn fetch(&self) -> Result<UrlResourceContent, UrlResourceError>;
+
+    /// Streams the content straight from the source without caching or buffering it. When an
+    /// expected hash is configured the stream ends with an error if the content did not match.
+    async fn fetch_stream(&self) -> Result<UrlResourceStream, UrlResourceError>;
 }
 
 #[derive(Clone)]
 pub struct UrlResourceContent {
     pub data: Bytes,
-    pub hash: Option<String>,
+    pub metadata: UrlResourceMetadata,
+    /// Digests for the configured [config::UrlResource::hash] algorithms.
+    #[cfg(feature = "hash")]
+    pub digests: Vec<hash::Digest>,
+}
+
+#[cfg(feature = "hash")]
+impl UrlResourceContent {
+    /// The [UrlResourceContent::digests] as an SRI `integrity` value, if any.
+    pub fn integrity(&self) -> Option<String> {
+        (!self.digests.is_empty()).then(|| hash::integrity(&self.digests))
+    }
+}
+
+/// Where and when content was read, from whatever the scheme provides.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UrlResourceMetadata {
+    /// When the content was read or last revalidated.
+    pub fetched_at: SystemTime,
+    /// When the cached content will be refetched.
+    pub expires_at: SystemTime,
+    /// The URL the content was read from, after following any redirects.
+    pub url: Url,
+    /// The HTTP `Content-Type`.
+    pub content_type: Option<String>,
+    /// The HTTP `ETag`.
+    pub etag: Option<String>,
+    /// The HTTP `Last-Modified`.
+    pub last_modified: Option<String>,
+    /// The modification time of a file.
+    pub modified: Option<SystemTime>,
+    /// Whether this is previous content served because refetching failed, as configured by
+    /// [config::UrlResource::serve_stale_on_error].
+    pub stale: bool,
 }
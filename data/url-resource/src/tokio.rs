@@ -1,20 +1,54 @@
-use std::{borrow::BorrowMut, ops::Deref, os::unix::fs::MetadataExt, time::Duration};
+#[cfg(feature = "notify")]
+mod file_watch;
+
+use std::{
+    ops::Deref,
+    os::unix::fs::MetadataExt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+#[cfg(feature = "hash")]
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
 
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use tokio::{
     fs::File,
-    io::AsyncReadExt,
     sync::{mpsc, oneshot, watch},
-    time::{sleep_until, Instant},
+    time::{sleep, sleep_until, Instant},
 };
-use tokio_stream::{wrappers::WatchStream, StreamExt};
-use tracing::{debug, error, info_span, trace, Instrument};
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+use tokio_util::{io::ReaderStream, sync::CancellationToken};
+use tracing::{debug, error, info_span, trace, warn, Instrument};
 use url::Url;
 
-use crate::{config, error::UrlResourceError, UrlResourceContent, UrlResourceFetch};
+#[cfg(feature = "hash")]
+use crate::hash::{self, Digest, Hasher};
+use crate::{
+    config,
+    error::{UrlResourceError, UrlResourceErrorReason},
+    UrlResourceContent, UrlResourceFetch, UrlResourceMetadata, UrlResourceStream,
+};
 
 const DEFAULT_MPSC_CHANNEL_SIZE: usize = 8;
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+/// Caps the buffer allocated up front from a reported size, which may be wrong.
+const MAX_PREALLOCATE: u64 = 64 * 1024 * 1024;
+const INVALIDATE_WHILE_FETCHING_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const DEFAULT_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(10);
+const DEFAULT_RETRY_MULTIPLIER: f64 = 2.0;
+const DEFAULT_RETRY_JITTER: f64 = 0.2;
+#[cfg(feature = "http")]
+const DEFAULT_HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(feature = "http")]
+const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+#[cfg(feature = "http")]
+const DEFAULT_HTTP_MAX_REDIRECTS: usize = 10;
 
 #[derive(strum_macros::EnumDiscriminants)]
 #[strum_discriminants(derive(strum::AsRefStr))]
@@ -24,26 +58,135 @@ enum UrlResourceCommand {
     Fetch {
         respond_to: oneshot::Sender<watch::Receiver<FetchStatus>>,
     },
+    /// Fetches if expired, like [UrlResourceCommand::Fetch] without a caller waiting.
+    Refresh,
     Clear,
+    /// Clears the cache even if not expired, such as when the file changed.
+    Invalidate,
 }
 
 #[derive(Clone, strum_macros::EnumDiscriminants)]
 #[strum_discriminants(derive(strum::AsRefStr))]
 #[strum_discriminants(name(FetchStatusType))]
 #[strum_discriminants(strum(serialize_all = "SCREAMING_SNAKE_CASE"))]
+// Only a single status is held, in the watch channel.
+#[allow(clippy::large_enum_variant)]
 enum FetchStatus {
     NotFetched,
     Fetching,
     Fetched {
-        result: Result<UrlResourceContent, UrlResourceError>,
+        result: Result<Fetched, UrlResourceError>,
         expiration: Instant,
     },
 }
 
+#[derive(Clone)]
+struct Fetched {
+    content: UrlResourceContent,
+    fetched_at: Instant,
+}
+
+#[derive(Clone)]
+struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    retryable: Vec<UrlResourceErrorReason>,
+}
+
+impl RetryPolicy {
+    fn new(options: Option<&config::RetryOptions>) -> Result<Self, UrlResourceError> {
+        let Some(options) = options else {
+            return Ok(Self {
+                max_attempts: 1,
+                initial_backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+                multiplier: 1.0,
+                jitter: 0.0,
+                retryable: Vec::new(),
+            });
+        };
+        let policy = Self {
+            max_attempts: options.max_attempts.unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS),
+            initial_backoff: options
+                .initial_backoff
+                .unwrap_or(DEFAULT_RETRY_INITIAL_BACKOFF),
+            max_backoff: options.max_backoff.unwrap_or(DEFAULT_RETRY_MAX_BACKOFF),
+            multiplier: options.multiplier.unwrap_or(DEFAULT_RETRY_MULTIPLIER),
+            jitter: options.jitter.unwrap_or(DEFAULT_RETRY_JITTER),
+            retryable: options.retryable.clone().unwrap_or_else(|| {
+                vec![
+                    UrlResourceErrorReason::ServiceUnavailable,
+                    UrlResourceErrorReason::ResourceReadError,
+                ]
+            }),
+        };
+        if policy.max_attempts == 0 {
+            return Err(UrlResourceError::new_failed_precondition(
+                "Retry max_attempts must be at least 1".to_owned(),
+            ));
+        }
+        if !(policy.multiplier >= 1.0 && policy.multiplier.is_finite()) {
+            return Err(UrlResourceError::new_failed_precondition(format!(
+                "Retry multiplier `{}` must be at least 1",
+                policy.multiplier
+            )));
+        }
+        if !(0.0..=1.0).contains(&policy.jitter) {
+            return Err(UrlResourceError::new_failed_precondition(format!(
+                "Retry jitter `{}` must be between 0 and 1",
+                policy.jitter
+            )));
+        }
+        Ok(policy)
+    }
+
+    fn retries(&self, attempt: u32, err: &UrlResourceError) -> bool {
+        attempt < self.max_attempts && self.retryable.contains(&UrlResourceErrorReason::from(err))
+    }
+
+    /// Delay after the given failed attempt, starting from 1.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt - 1).unwrap_or(i32::MAX);
+        let backoff = Duration::try_from_secs_f64(
+            self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent),
+        )
+        .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+        backoff.mul_f64(1.0 - self.jitter * rand::random::<f64>())
+    }
+}
+
 #[derive(Clone)]
 pub struct UrlResource {
     commands_tx: mpsc::Sender<UrlResourceCommand>,
+    source: Arc<Source>,
+}
+
+/// Where and how a resource is read, shared by cached fetches and [UrlResource::fetch_stream].
+struct Source {
     url: Url,
+    #[cfg(feature = "hash")]
+    hash: Vec<config::UrlResourceHash>,
+    #[cfg(feature = "hash")]
+    expected_hash: Vec<Digest>,
+    #[cfg(feature = "http")]
+    http_client: reqwest::Client,
+    max_size: Option<u64>,
+}
+
+/// A resource opened for reading.
+enum Opened {
+    /// The server responded `304 Not Modified` to the conditional request.
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    NotModified(UrlResourceMetadata),
+    Body {
+        /// The size reported before reading, if any.
+        size: Option<u64>,
+        chunks: UrlResourceStream,
+        metadata: UrlResourceMetadata,
+    },
 }
 
 impl UrlResource {
@@ -65,17 +208,63 @@ impl UrlResource {
         );
 
         let (watch_tx, watch_rx) = watch::channel(FetchStatus::NotFetched);
+        let ttl = config.cache_ttl.unwrap_or(Duration::from_secs(15 * 60));
 
-        let actor = UrlResourceActor {
+        let source = Arc::new(Source {
             url: config.url.clone(),
-            #[cfg(feature = "sha256")]
+            #[cfg(feature = "hash")]
             hash: config.hash,
-            commands_tx: commands_tx.clone(),
+            #[cfg(feature = "hash")]
+            expected_hash: hash::strongest(hash::parse_integrity(
+                config.expected_hash.as_deref().unwrap_or_default(),
+            )?),
+            #[cfg(feature = "http")]
+            http_client: http_client(&config.http.unwrap_or_default())?,
+            max_size: config.max_size,
+        });
+
+        let shutdown = CancellationToken::new();
+        let actor = UrlResourceActor {
+            source: source.clone(),
+            commands_tx: commands_tx.downgrade(),
             commands_rx,
             watch_tx,
             watch_rx,
-            ttl: config.cache_ttl.unwrap_or(Duration::from_secs(15 * 60)),
+            ttl,
+            error_ttl: config.error_cache_ttl.unwrap_or(ttl),
+            serve_stale_on_error: config.serve_stale_on_error,
+            retry: RetryPolicy::new(config.retry.as_ref())?,
+            background_refresh: config.background_refresh.unwrap_or_default(),
+            stale: None,
+            shutdown: shutdown.clone(),
         };
+        if actor.background_refresh {
+            let _ = commands_tx.try_send(UrlResourceCommand::Refresh);
+        }
+
+        #[cfg(not(feature = "notify"))]
+        if config.watch.unwrap_or_default() {
+            return Err(UrlResourceError::new_failed_precondition(
+                "Watching requires the `notify` feature".to_owned(),
+            ));
+        }
+        #[cfg(feature = "notify")]
+        if config.watch.unwrap_or_default() {
+            let path = match config.url.scheme() {
+                "file" => config.url.to_file_path().map_err(|_| {
+                    UrlResourceError::new_failed_precondition(format!(
+                        "Configured URL resource invalid: {}",
+                        config.url
+                    ))
+                })?,
+                scheme => {
+                    return Err(UrlResourceError::new_failed_precondition(format!(
+                        "Watching URL scheme `{scheme}` is not supported"
+                    )))
+                }
+            };
+            file_watch::spawn(path, commands_tx.downgrade(), shutdown.clone())?;
+        }
 
         let actor = tokio::spawn(actor.run());
 
@@ -92,17 +281,48 @@ impl UrlResource {
 
         Ok(Self {
             commands_tx,
-            url: config.url,
+            source,
         })
     }
 }
 
+impl UrlResource {
+    /// Streams the content once fetched and then whenever it changes. Failed fetches are skipped
+    /// and stale content is not repeated. New content is only fetched by
+    /// [config::UrlResource::background_refresh] or by calls to [UrlResource::fetch], and the
+    /// stream ends once every handle to the resource is dropped.
+    pub async fn subscribe(
+        &self,
+    ) -> Result<impl Stream<Item = UrlResourceContent> + Send + 'static, UrlResourceError> {
+        let (respond_to, result) = oneshot::channel();
+        self.commands_tx
+            .send(UrlResourceCommand::Fetch { respond_to })
+            .await?;
+        let mut last: Option<Bytes> = None;
+        Ok(
+            WatchStream::new(result.await?).filter_map(move |status| match status {
+                FetchStatus::Fetched {
+                    result: Ok(fetched),
+                    expiration: _expiration,
+                } if last.as_ref() != Some(&fetched.content.data) => {
+                    last = Some(fetched.content.data.clone());
+                    Some(fetched.content)
+                }
+                _ => None,
+            }),
+        )
+    }
+}
+
 #[async_trait]
 impl UrlResourceFetch for UrlResource {
     async fn fetch(&self) -> Result<UrlResourceContent, UrlResourceError> {
         async move {
             let (respond_to, result) = oneshot::channel();
-            trace!(url = self.url.as_str(), "Sending UrlResourceCommand::Fetch");
+            trace!(
+                url = self.source.url.as_str(),
+                "Sending UrlResourceCommand::Fetch"
+            );
             self.commands_tx
                 .send(UrlResourceCommand::Fetch { respond_to })
                 .await
@@ -115,18 +335,24 @@ impl UrlResourceFetch for UrlResource {
                         expiration: _expiration,
                     } => {
                         trace!(
-                            url = self.url.as_str(),
+                            url = self.source.url.as_str(),
                             ok = result.is_ok(),
                             "Received FetchStatus::Fetched"
                         );
-                        return result;
+                        return result.map(|fetched| fetched.content);
                     }
                     FetchStatus::Fetching => {
-                        trace!(url = self.url.as_str(), "Received FetchStatus::Fetching");
+                        trace!(
+                            url = self.source.url.as_str(),
+                            "Received FetchStatus::Fetching"
+                        );
                         continue;
                     }
                     FetchStatus::NotFetched => {
-                        trace!(url = self.url.as_str(), "Received FetchStatus::NotFetched");
+                        trace!(
+                            url = self.source.url.as_str(),
+                            "Received FetchStatus::NotFetched"
+                        );
                         continue;
                     }
                 }
@@ -139,196 +365,541 @@ impl UrlResourceFetch for UrlResource {
         .instrument(info_span!("fetch"))
         .await
     }
+
+    async fn fetch_stream(&self) -> Result<UrlResourceStream, UrlResourceError> {
+        let chunks = match self.source.open(None).await? {
+            Opened::Body { chunks, .. } => chunks,
+            Opened::NotModified(_) => {
+                return Err(UrlResourceError::new_resource_read_error(
+                    "Unexpected HTTP status 304 Not Modified".to_owned(),
+                ))
+            }
+        };
+        #[cfg(feature = "hash")]
+        if !self.source.expected_hash.is_empty() {
+            return Ok(Box::pin(VerifyingStream {
+                chunks,
+                hasher: Some(Hasher::new(
+                    self.source
+                        .expected_hash
+                        .iter()
+                        .map(|digest| digest.algorithm),
+                )),
+                source: self.source.clone(),
+            }));
+        }
+        Ok(chunks)
+    }
+}
+
+/// Hashes chunks as they pass, ending with an error if the expected hash does not match.
+#[cfg(feature = "hash")]
+struct VerifyingStream {
+    chunks: UrlResourceStream,
+    hasher: Option<Hasher>,
+    source: Arc<Source>,
+}
+
+#[cfg(feature = "hash")]
+impl Stream for VerifyingStream {
+    type Item = Result<Bytes, UrlResourceError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(hasher) = this.hasher.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match ready!(this.chunks.as_mut().poll_next(cx)) {
+            Some(Ok(chunk)) => {
+                hasher.update(&chunk);
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Some(Err(err)) => {
+                this.hasher = None;
+                Poll::Ready(Some(Err(err)))
+            }
+            None => {
+                let digests = this.hasher.take().map(Hasher::finalize).unwrap_or_default();
+                Poll::Ready(this.source.verify(&digests).err().map(Err))
+            }
+        }
+    }
 }
 
 struct UrlResourceActor {
-    url: Url,
-    #[cfg(feature = "sha256")]
-    hash: Option<config::UrlResourceHash>,
-    commands_tx: mpsc::Sender<UrlResourceCommand>,
+    source: Arc<Source>,
+    /// Weak so that the actor stops once every [UrlResource] handle is dropped.
+    commands_tx: mpsc::WeakSender<UrlResourceCommand>,
     commands_rx: mpsc::Receiver<UrlResourceCommand>,
     watch_tx: watch::Sender<FetchStatus>,
     watch_rx: watch::Receiver<FetchStatus>,
     ttl: Duration,
+    error_ttl: Duration,
+    serve_stale_on_error: Option<Duration>,
+    retry: RetryPolicy,
+    background_refresh: bool,
+    /// The last successful fetch, kept after the cache is cleared so that it can be revalidated.
+    stale: Option<Fetched>,
+    /// Cancelled when the actor stops, ending the tasks waiting to send it commands.
+    shutdown: CancellationToken,
 }
 
 impl UrlResourceActor {
     async fn run(mut self) {
+        let _shutdown = self.shutdown.clone().drop_guard();
         while let Some(command) = self.commands_rx.recv().await {
             let state = FetchStatusType::from(self.watch_tx.borrow().deref());
             let command_type = UrlResourceCommandType::from(&command);
 
             trace!(
-                url = self.url.as_str(),
+                url = self.source.url.as_str(),
                 state = state.as_ref(),
                 command = command_type.as_ref(),
                 "Processing command"
             );
 
             match command {
-                UrlResourceCommand::Clear => {
-                    let needs_clear = match self.watch_tx.borrow().deref() {
-                        FetchStatus::Fetched {
-                            result: _,
-                            expiration,
-                        } => {
+                command @ (UrlResourceCommand::Clear | UrlResourceCommand::Invalidate) => {
+                    let invalidate = matches!(command, UrlResourceCommand::Invalidate);
+                    let (needs_clear, stale) = match self.watch_tx.borrow().deref() {
+                        FetchStatus::Fetched { result, expiration } => {
                             let now = Instant::now();
-                            let expiration = *expiration;
-                            expiration < now
+                            let expired = invalidate || *expiration <= now;
+                            (expired, result.as_ref().ok().filter(|_| expired).cloned())
                         }
-                        _ => false,
+                        _ => (false, None),
                     };
 
+                    // The fetch in progress may have read the content from before the change.
+                    if invalidate && state == FetchStatusType::Fetching {
+                        let commands_tx = self.commands_tx.clone();
+                        tokio::spawn(async move {
+                            sleep(INVALIDATE_WHILE_FETCHING_DELAY).await;
+                            if let Some(commands_tx) = commands_tx.upgrade() {
+                                let _ = commands_tx.send(UrlResourceCommand::Invalidate).await;
+                            }
+                        });
+                    }
+
                     if needs_clear {
+                        if stale.is_some() {
+                            self.stale = stale;
+                        }
                         trace!(
-                            url = self.url.as_str(),
+                            url = self.source.url.as_str(),
                             state = state.as_ref(),
                             command = command_type.as_ref(),
                             needs_clear,
                             "Clearing cache"
                         );
                         let _ = self.watch_tx.send(FetchStatus::NotFetched);
+                        if invalidate && self.background_refresh {
+                            if let Some(commands_tx) = self.commands_tx.upgrade() {
+                                let _ = commands_tx.try_send(UrlResourceCommand::Refresh);
+                            }
+                        }
                     }
                 }
-                UrlResourceCommand::Fetch { respond_to } => {
-                    let watch_tx = self.watch_tx.borrow_mut();
-
-                    match watch_tx.borrow().deref() {
-                        FetchStatus::NotFetched => {
-                            let _ = respond_to.send(self.watch_rx.clone());
-                        }
-                        FetchStatus::Fetching => {
-                            let _ = respond_to.send(self.watch_rx.clone());
-                            continue;
-                        }
-                        FetchStatus::Fetched {
-                            result: _,
-                            expiration,
-                        } => {
+                command @ (UrlResourceCommand::Fetch { .. } | UrlResourceCommand::Refresh) => {
+                    // The status must not be borrowed while sending, which would deadlock.
+                    let (needs_fetch, previous) = match self.watch_tx.borrow().deref() {
+                        FetchStatus::NotFetched => (true, self.stale.clone()),
+                        FetchStatus::Fetching => (false, None),
+                        FetchStatus::Fetched { result, expiration } => {
                             let now = Instant::now();
-                            let expiration = *expiration;
-                            let expired = expiration < now;
+                            let expired = *expiration <= now;
                             trace!(
-                                url = self.url.as_str(),
+                                url = self.source.url.as_str(),
                                 state = state.as_ref(),
                                 command = command_type.as_ref(),
                                 expired,
                                 "Processing fetch"
                             );
-                            if expired {
-                                // Need to fetch a new value.
-                                let _ = watch_tx.send(FetchStatus::Fetching);
-                            } else {
-                                // Return the existing value.
-                                let _ = respond_to.send(self.watch_rx.clone());
-                                continue;
-                            }
+                            let previous = result.as_ref().ok().or(self.stale.as_ref());
+                            (expired, previous.filter(|_| expired).cloned())
                         }
+                    };
+
+                    // Mark as fetching before responding so that the caller does not receive an
+                    // expired value.
+                    if needs_fetch {
+                        let _ = self.watch_tx.send(FetchStatus::Fetching);
+                    }
+                    if let UrlResourceCommand::Fetch { respond_to } = command {
+                        let _ = respond_to.send(self.watch_rx.clone());
+                    }
+                    if !needs_fetch {
+                        continue;
                     }
 
-                    let url = self.url.clone();
-                    #[cfg(feature = "sha256")]
-                    let hash = self.hash.clone();
+                    let source = self.source.clone();
                     let watch_tx = self.watch_tx.clone();
                     let commands_tx = self.commands_tx.clone();
                     let ttl = self.ttl;
+                    let error_ttl = self.error_ttl;
+                    let serve_stale_on_error = self.serve_stale_on_error;
+                    let retry = self.retry.clone();
+                    let background_refresh = self.background_refresh;
+                    let shutdown = self.shutdown.clone();
                     tokio::spawn(async move {
-                        let tracing_url = url.clone();
+                        let tracing_url = source.url.clone();
                         let scheme = tracing_url.scheme();
                         let span = info_span!("fetch_url", scheme, url = tracing_url.as_str());
 
-                        let result = fetch_url(
-                            url,
-                            #[cfg(feature = "sha256")]
-                            hash,
-                        )
-                        .instrument(span)
-                        .await
-                        .inspect_err(|err| {
-                            error!(
-                                error = err.to_string(),
-                                url = tracing_url.as_str(),
-                                "Failed to fetch URL"
-                            )
-                        });
+                        let mut attempt = 1;
+                        let result = loop {
+                            let result = source
+                                .fetch(previous.clone())
+                                .instrument(span.clone())
+                                .await
+                                .inspect_err(|err| {
+                                    error!(
+                                        error = err.to_string(),
+                                        url = tracing_url.as_str(),
+                                        attempt,
+                                        "Failed to fetch URL"
+                                    )
+                                });
+                            match result {
+                                Err(err) if retry.retries(attempt, &err) => {
+                                    let backoff = retry.backoff(attempt);
+                                    debug!(
+                                        url = tracing_url.as_str(),
+                                        attempt,
+                                        backoff_millis = backoff.as_millis() as u64,
+                                        "Retrying fetch"
+                                    );
+                                    sleep(backoff).await;
+                                    attempt += 1;
+                                }
+                                result => break result,
+                            }
+                        };
 
                         let now = Instant::now();
-                        let expiration = now + ttl;
+                        let (mut result, expiration) = match result {
+                            Ok(fetched) => (Ok(fetched), now + ttl),
+                            Err(err) => {
+                                let stale_until = previous
+                                    .as_ref()
+                                    .zip(serve_stale_on_error)
+                                    .map(|(previous, window)| previous.fetched_at + ttl + window)
+                                    .filter(|stale_until| *stale_until > now);
+                                match (previous, stale_until) {
+                                    (Some(mut previous), Some(stale_until)) => {
+                                        warn!(
+                                            error = err.to_string(),
+                                            url = tracing_url.as_str(),
+                                            "Serving stale content"
+                                        );
+                                        previous.content.metadata.stale = true;
+                                        (Ok(previous), (now + error_ttl).min(stale_until))
+                                    }
+                                    _ => (Err(err), now + error_ttl),
+                                }
+                            }
+                        };
+
+                        if let Ok(fetched) = &mut result {
+                            fetched.content.metadata.expires_at =
+                                SystemTime::now() + (expiration - now);
+                        }
 
                         debug!(
                             url = tracing_url.as_str(),
-                            ttl_seconds = ttl.as_secs(),
+                            ok = result.is_ok(),
+                            ttl_seconds = (expiration - now).as_secs(),
                             "Fetched URL"
                         );
                         let _ = watch_tx.send(FetchStatus::Fetched { result, expiration });
-                        sleep_until(expiration).await;
+                        drop(watch_tx);
+                        let commands_tx = tokio::select! {
+                            _ = sleep_until(expiration) => commands_tx.upgrade(),
+                            _ = shutdown.cancelled() => None,
+                        };
+                        let Some(commands_tx) = commands_tx else {
+                            return;
+                        };
 
-                        debug!(
-                            url = tracing_url.as_str(),
-                            ttl_seconds = ttl.as_secs(),
-                            "TTL reached clearing cache"
-                        );
-                        let _ = commands_tx.send(UrlResourceCommand::Clear).await;
+                        if background_refresh {
+                            debug!(url = tracing_url.as_str(), "TTL reached refreshing");
+                            let _ = commands_tx.send(UrlResourceCommand::Refresh).await;
+                        } else {
+                            debug!(url = tracing_url.as_str(), "TTL reached clearing cache");
+                            let _ = commands_tx.send(UrlResourceCommand::Clear).await;
+                        }
                     });
                 }
             }
         }
         trace!(
-            url = self.url.as_str(),
+            url = self.source.url.as_str(),
             "UrlResourceActor command_rx queue closed"
         );
     }
 }
 
-async fn fetch_url(
-    url: Url,
-    #[cfg(feature = "sha256")] hash: Option<config::UrlResourceHash>,
-) -> Result<UrlResourceContent, UrlResourceError> {
-    let data = match url.scheme() {
-        "file" => {
-            let file_path = url.to_file_path().map_err(|_| {
-                UrlResourceError::new_failed_precondition(format!(
-                    "Configured URL resource invalid: {url}"
-                ))
-            })?;
+impl Source {
+    async fn open(
+        &self,
+        #[cfg_attr(not(feature = "http"), allow(unused_variables))] conditional: Option<
+            &UrlResourceMetadata,
+        >,
+    ) -> Result<Opened, UrlResourceError> {
+        match self.url.scheme() {
+            "file" => open_file(&self.url).await,
+            #[cfg(feature = "http")]
+            "http" | "https" => open_http(&self.http_client, self.url.clone(), conditional).await,
+            scheme => Err(UrlResourceError::new_unsupported_scheme(scheme.to_owned())),
+        }
+    }
 
-            let mut file = File::open(&file_path)
-                .await
-                .map_err(|err| UrlResourceError::new_resource_not_found(err.to_string()))?;
-            let metadata = file
-                .metadata()
-                .await
-                .map_err(|err| UrlResourceError::new_resource_read_error(err.to_string()))?;
-            let size = usize::try_from(metadata.size()).map_err(|_| {
-                UrlResourceError::new_resource_read_error(format!(
-                    "Cannot read resource since size `{}` greater than usize max `{}`",
-                    metadata.size(),
-                    usize::MAX
-                ))
-            })?;
-            let mut data: Vec<u8> = Vec::with_capacity(size);
-            file.read_to_end(&mut data)
-                .await
-                .map_err(|err| UrlResourceError::new_resource_read_error(err.to_string()))?;
+    /// Reads the whole resource, revalidating the previous content if possible.
+    async fn fetch(&self, previous: Option<Fetched>) -> Result<Fetched, UrlResourceError> {
+        let conditional = previous.as_ref().map(|previous| &previous.content.metadata);
+        let (size, mut chunks, metadata) = match self.open(conditional).await? {
+            Opened::Body {
+                size,
+                chunks,
+                metadata,
+            } => (size, chunks, metadata),
+            Opened::NotModified(metadata) => {
+                let previous = previous.ok_or_else(|| {
+                    UrlResourceError::new_resource_read_error(
+                        "HTTP status 304 Not Modified without cached content".to_owned(),
+                    )
+                })?;
+                debug!("Revalidated cached content");
+                let previous_metadata = previous.content.metadata;
+                return Ok(Fetched {
+                    content: UrlResourceContent {
+                        metadata: UrlResourceMetadata {
+                            content_type: metadata.content_type.or(previous_metadata.content_type),
+                            etag: metadata.etag.or(previous_metadata.etag),
+                            last_modified: metadata
+                                .last_modified
+                                .or(previous_metadata.last_modified),
+                            ..metadata
+                        },
+                        ..previous.content
+                    },
+                    fetched_at: Instant::now(),
+                });
+            }
+        };
 
-            data
+        let max_size = self.max_size.unwrap_or(u64::MAX);
+        if size.is_some_and(|size| size > max_size) {
+            return Err(UrlResourceError::new_resource_too_large(max_size));
         }
-        scheme => return Err(UrlResourceError::new_unsupported_scheme(scheme.to_owned())),
-    };
 
-    // NOTE: Could be doing hash in parallel with download.
-    #[cfg(feature = "sha256")]
-    let hash = hash.map(|hash| match hash {
-        config::UrlResourceHash::Sha256 => format!("sha256:{}", sha256::digest(&data)),
+        #[cfg(feature = "hash")]
+        let mut hasher = Hasher::new(
+            self.hash
+                .iter()
+                .copied()
+                .chain(self.expected_hash.iter().map(|digest| digest.algorithm)),
+        );
+        let capacity = size.unwrap_or_default().min(MAX_PREALLOCATE);
+        let mut data = BytesMut::with_capacity(usize::try_from(capacity).unwrap_or_default());
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if (data.len() + chunk.len()) as u64 > max_size {
+                return Err(UrlResourceError::new_resource_too_large(max_size));
+            }
+            #[cfg(feature = "hash")]
+            hasher.update(&chunk);
+            data.extend_from_slice(&chunk);
+        }
+
+        #[cfg(feature = "hash")]
+        let digests = {
+            let digests = hasher.finalize();
+            self.verify(&digests)?;
+            digests
+                .into_iter()
+                .filter(|digest| self.hash.contains(&digest.algorithm))
+                .collect()
+        };
+
+        Ok(Fetched {
+            content: UrlResourceContent {
+                data: data.freeze(),
+                metadata,
+                #[cfg(feature = "hash")]
+                digests,
+            },
+            fetched_at: Instant::now(),
+        })
+    }
+
+    /// Checks that the computed digest matches any of the expected alternatives.
+    #[cfg(feature = "hash")]
+    fn verify(&self, digests: &[Digest]) -> Result<(), UrlResourceError> {
+        let Some(algorithm) = self.expected_hash.first().map(|digest| digest.algorithm) else {
+            return Ok(());
+        };
+        match digests.iter().find(|actual| actual.algorithm == algorithm) {
+            Some(actual) if self.expected_hash.contains(actual) => Ok(()),
+            actual => Err(UrlResourceError::new_integrity_mismatch(
+                self.expected_hash
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                actual.map(ToString::to_string).unwrap_or_default(),
+            )),
+        }
+    }
+}
+
+/// Metadata for content read now, expiring once cached.
+fn fetched_metadata(url: Url) -> UrlResourceMetadata {
+    let now = SystemTime::now();
+    UrlResourceMetadata {
+        fetched_at: now,
+        expires_at: now,
+        url,
+        content_type: None,
+        etag: None,
+        last_modified: None,
+        modified: None,
+        stale: false,
+    }
+}
+
+async fn open_file(url: &Url) -> Result<Opened, UrlResourceError> {
+    let file_path = url.to_file_path().map_err(|_| {
+        UrlResourceError::new_failed_precondition(format!("Configured URL resource invalid: {url}"))
+    })?;
+
+    let file = File::open(&file_path)
+        .await
+        .map_err(|err| UrlResourceError::new_resource_not_found(err.to_string()))?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|err| UrlResourceError::new_resource_read_error(err.to_string()))?;
+
+    Ok(Opened::Body {
+        size: Some(metadata.size()),
+        chunks: Box::pin(
+            ReaderStream::with_capacity(file, FILE_CHUNK_SIZE).map(|chunk| {
+                chunk.map_err(|err| UrlResourceError::new_resource_read_error(err.to_string()))
+            }),
+        ),
+        metadata: UrlResourceMetadata {
+            modified: metadata.modified().ok(),
+            ..fetched_metadata(url.clone())
+        },
+    })
+}
+
+#[cfg(feature = "http")]
+fn http_client(options: &config::HttpOptions) -> Result<reqwest::Client, UrlResourceError> {
+    let max_redirects = options.max_redirects.unwrap_or(DEFAULT_HTTP_MAX_REDIRECTS);
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            attempt.error(format!("Exceeded {max_redirects} redirects"))
+        } else if attempt.url().scheme() == "http"
+            && attempt.previous().iter().any(|url| url.scheme() == "https")
+        {
+            attempt.error("Refused redirect from https to http")
+        } else {
+            attempt.follow()
+        }
     });
+    reqwest::Client::builder()
+        .connect_timeout(
+            options
+                .connect_timeout
+                .unwrap_or(DEFAULT_HTTP_CONNECT_TIMEOUT),
+        )
+        .timeout(options.timeout.unwrap_or(DEFAULT_HTTP_TIMEOUT))
+        .redirect(redirect)
+        .build()
+        .map_err(|err| UrlResourceError::new_failed_precondition(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+async fn open_http(
+    http_client: &reqwest::Client,
+    url: Url,
+    conditional: Option<&UrlResourceMetadata>,
+) -> Result<Opened, UrlResourceError> {
+    use reqwest::{header, StatusCode};
+
+    let mut request = http_client.get(url);
+    if let Some(conditional) = conditional {
+        if let Some(etag) = &conditional.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &conditional.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
 
-    Ok(UrlResourceContent {
-        data: Bytes::from(data),
-        #[cfg(feature = "sha256")]
-        hash,
+    let response = request.send().await?;
+    let status = response.status();
+    let header_value = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let metadata = UrlResourceMetadata {
+        content_type: header_value(header::CONTENT_TYPE),
+        etag: header_value(header::ETAG),
+        last_modified: header_value(header::LAST_MODIFIED),
+        ..fetched_metadata(response.url().clone())
+    };
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Opened::NotModified(metadata));
+    }
+    if !status.is_success() {
+        let message = format!("HTTP status {status} for {}", response.url());
+        return Err(match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => {
+                UrlResourceError::new_resource_not_found(message)
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                UrlResourceError::new_resource_access_denied(message)
+            }
+            StatusCode::TOO_MANY_REQUESTS => UrlResourceError::new_service_unavailable(message),
+            status if status.is_server_error() => {
+                UrlResourceError::new_service_unavailable(message)
+            }
+            _ => UrlResourceError::new_resource_read_error(message),
+        });
+    }
+    Ok(Opened::Body {
+        size: response.content_length(),
+        chunks: Box::pin(
+            response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(UrlResourceError::from)),
+        ),
+        metadata,
     })
 }
 
+#[cfg(feature = "http")]
+impl From<reqwest::Error> for UrlResourceError {
+    fn from(value: reqwest::Error) -> Self {
+        // The source carries the cause, such as the refused redirect or connection error.
+        let message = match std::error::Error::source(&value) {
+            Some(source) => format!("{value}: {source}"),
+            None => value.to_string(),
+        };
+        if value.is_timeout() || value.is_connect() {
+            UrlResourceError::new_service_unavailable(message)
+        } else {
+            UrlResourceError::new_resource_read_error(message)
+        }
+    }
+}
+
 impl<T> From<mpsc::error::SendError<T>> for UrlResourceError {
     fn from(value: mpsc::error::SendError<T>) -> Self {
         UrlResourceError::new_service_unavailable(value.to_string())
@@ -349,14 +920,24 @@ impl From<oneshot::error::RecvError> for UrlResourceError {
 
 #[cfg(test)]
 mod test {
-    use std::time::Duration;
+    use std::{
+        pin::pin,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
-    use tokio::{fs::write, time};
+    use tokio::{
+        fs::{remove_file, write},
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time,
+    };
+    use tokio_stream::StreamExt;
     use tracing_test::traced_test;
     use url::Url;
 
     use crate::{
-        config::{self, TokioUrlResourceProvider},
+        config,
         error::{UrlResourceError, UrlResourceErrorReason},
         UrlResourceFetch,
     };
@@ -368,12 +949,8 @@ mod test {
     #[tokio::test]
     async fn ftp_unsupported() {
         let config = config::UrlResource {
-            url: Url::parse("ftp://example.com/hello.txt").unwrap(),
             cache_ttl: Some(Duration::from_secs(60)),
-            hash: None,
-            provider: config::UrlResourceProvider::Tokio(TokioUrlResourceProvider {
-                mpsc_channel_size: None,
-            }),
+            ..config::UrlResource::new(Url::parse("ftp://example.com/hello.txt").unwrap())
         };
 
         let url_resource = UrlResource::new(config).unwrap();
@@ -392,12 +969,9 @@ mod test {
         let file_path = temp_dir.path().join("hello.txt");
 
         let config = config::UrlResource {
-            url: Url::from_file_path(&file_path).unwrap(),
             cache_ttl: Some(Duration::from_secs(60)),
-            hash: Some(config::UrlResourceHash::Sha256),
-            provider: config::UrlResourceProvider::Tokio(TokioUrlResourceProvider {
-                mpsc_channel_size: None,
-            }),
+            hash: vec![config::UrlResourceHash::Sha256],
+            ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
         };
 
         let url_resource = UrlResource::new(config).unwrap();
@@ -415,7 +989,356 @@ mod test {
             String::from_utf8(content.data.to_vec()).unwrap(),
             file_content
         );
-        assert_eq!(content.hash.as_deref(), Some(expected_hash));
+        assert_eq!(content.digests[0].to_string(), expected_hash);
+
+        let metadata = content.metadata;
+        assert_eq!(metadata.url, Url::from_file_path(&file_path).unwrap());
+        assert_eq!(
+            metadata.modified,
+            Some(std::fs::metadata(&file_path).unwrap().modified().unwrap())
+        );
+        assert!(metadata.expires_at >= metadata.fetched_at + Duration::from_secs(60));
+        assert_eq!(metadata.content_type, None);
+        assert!(!metadata.stale);
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn expected_hash_verified() {
+        let cache_ttl = Duration::from_secs(60);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("hello.txt");
+        let resource = |expected_hash: &str| {
+            UrlResource::new(config::UrlResource {
+                cache_ttl: Some(cache_ttl),
+                expected_hash: Some(expected_hash.to_owned()),
+                ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
+            })
+        };
+
+        for invalid in [
+            "md5:abc",
+            "SHA256:d9014c4624844aa5bac314773d6b689ad467fa4e1d1a50a1b8a99d5a95f72ff5",
+            "sha256:d9014c",
+        ] {
+            assert_eq!(
+                resource(invalid)
+                    .err()
+                    .map(|err| UrlResourceErrorReason::from(&err)),
+                Some(UrlResourceErrorReason::FailedPrecondition),
+                "{invalid}"
+            );
+        }
+
+        let url_resource =
+            resource("sha256:D9014C4624844AA5BAC314773D6B689AD467FA4E1D1A50A1B8A99D5A95F72FF5 sha256-2QFMRiSESqW6wxR3PWtomtRn+k4dGlChuKmdWpX3L/U=")
+                .unwrap();
+
+        write(&file_path, b"Tampered!\n").await.unwrap();
+        assert_eq!(
+            url_resource
+                .fetch()
+                .await
+                .err()
+                .map(|err| UrlResourceErrorReason::from(&err)),
+            Some(UrlResourceErrorReason::IntegrityMismatch)
+        );
+
+        write(&file_path, b"Hello, world!\n").await.unwrap();
+        time::advance(cache_ttl + Duration::from_millis(1)).await;
+        let content = url_resource.fetch().await.unwrap();
+        assert_eq!(content.data.as_ref(), b"Hello, world!\n");
+        assert!(content.digests.is_empty());
+
+        // Digests of one algorithm are alternatives, e.g. while rotating content.
+        let rotating = resource(
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU= sha256-2QFMRiSESqW6wxR3PWtomtRn+k4dGlChuKmdWpX3L/U=",
+        )
+        .unwrap();
+        assert!(rotating.fetch().await.is_ok());
+
+        // Only the strongest algorithm is checked.
+        #[cfg(feature = "sha512")]
+        {
+            let sha512_mismatch = resource(&format!(
+                "sha512-{} sha256-2QFMRiSESqW6wxR3PWtomtRn+k4dGlChuKmdWpX3L/U=",
+                "A".repeat(86) + "=="
+            ))
+            .unwrap();
+            assert_eq!(
+                sha512_mismatch
+                    .fetch()
+                    .await
+                    .err()
+                    .map(|err| UrlResourceErrorReason::from(&err)),
+                Some(UrlResourceErrorReason::IntegrityMismatch)
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn retry_works() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("hello.txt");
+        let resource = |retryable| {
+            UrlResource::new(config::UrlResource {
+                cache_ttl: Some(Duration::from_secs(60)),
+                retry: Some(config::RetryOptions {
+                    max_attempts: Some(3),
+                    initial_backoff: Some(Duration::from_secs(1)),
+                    jitter: Some(0.0),
+                    retryable: Some(vec![retryable]),
+                    ..Default::default()
+                }),
+                ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
+            })
+            .unwrap()
+        };
+
+        // Not retryable so fails immediately.
+        let start = time::Instant::now();
+        let result = resource(UrlResourceErrorReason::ServiceUnavailable)
+            .fetch()
+            .await;
+        assert_eq!(
+            result.err().map(|err| UrlResourceErrorReason::from(&err)),
+            Some(UrlResourceErrorReason::ResourceNotFound)
+        );
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // Fails after backing off 1 then 2 seconds.
+        let start = time::Instant::now();
+        let result = resource(UrlResourceErrorReason::ResourceNotFound)
+            .fetch()
+            .await;
+        assert_eq!(
+            result.err().map(|err| UrlResourceErrorReason::from(&err)),
+            Some(UrlResourceErrorReason::ResourceNotFound)
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+
+        // Succeeds on the second attempt once the file appears.
+        let start = time::Instant::now();
+        let url_resource = resource(UrlResourceErrorReason::ResourceNotFound);
+        let fetch = tokio::spawn(async move { url_resource.fetch().await });
+        time::sleep(Duration::from_millis(500)).await;
+        write(&file_path, b"Hello, world!\n").await.unwrap();
+        let content = fetch.await.unwrap().unwrap();
+        assert_eq!(content.data.as_ref(), b"Hello, world!\n");
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn serves_stale_on_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("hello.txt");
+        let url_resource = UrlResource::new(config::UrlResource {
+            cache_ttl: Some(Duration::from_secs(10)),
+            error_cache_ttl: Some(Duration::from_secs(1)),
+            serve_stale_on_error: Some(Duration::from_secs(30)),
+            ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
+        })
+        .unwrap();
+
+        write(&file_path, b"v1").await.unwrap();
+        let content = url_resource.fetch().await.unwrap();
+        assert_eq!(
+            (content.data.as_ref(), content.metadata.stale),
+            (&b"v1"[..], false)
+        );
+
+        remove_file(&file_path).await.unwrap();
+        time::advance(Duration::from_secs(11)).await;
+        let content = url_resource.fetch().await.unwrap();
+        assert_eq!(
+            (content.data.as_ref(), content.metadata.stale),
+            (&b"v1"[..], true)
+        );
+
+        // Refetched once the error cache ttl lapses.
+        write(&file_path, b"v2").await.unwrap();
+        time::advance(Duration::from_secs(2)).await;
+        let content = url_resource.fetch().await.unwrap();
+        assert_eq!(
+            (content.data.as_ref(), content.metadata.stale),
+            (&b"v2"[..], false)
+        );
+
+        // Failures are returned once the stale window lapses.
+        remove_file(&file_path).await.unwrap();
+        time::advance(Duration::from_secs(41)).await;
+        let result = url_resource.fetch().await;
+        assert_eq!(
+            result.err().map(|err| UrlResourceErrorReason::from(&err)),
+            Some(UrlResourceErrorReason::ResourceNotFound)
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn subscribe_with_background_refresh() {
+        // Files are changed synchronously so paused time cannot advance mid write.
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("hello.txt");
+        let url_resource = UrlResource::new(config::UrlResource {
+            cache_ttl: Some(Duration::from_secs(10)),
+            background_refresh: Some(true),
+            ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
+        })
+        .unwrap();
+
+        std::fs::write(&file_path, b"v1").unwrap();
+        let mut updates = pin!(url_resource.subscribe().await.unwrap());
+        let start = time::Instant::now();
+        assert_eq!(updates.next().await.unwrap().data.as_ref(), b"v1");
+
+        // Refreshed without fetching, skipping refreshes that do not change the content.
+        std::fs::write(&file_path, b"v2").unwrap();
+        assert_eq!(updates.next().await.unwrap().data.as_ref(), b"v2");
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert!(time::timeout(Duration::from_secs(25), updates.next())
+            .await
+            .is_err());
+
+        std::fs::remove_file(&file_path).unwrap();
+        time::sleep(Duration::from_secs(10)).await;
+        std::fs::write(&file_path, b"v3").unwrap();
+        assert_eq!(updates.next().await.unwrap().data.as_ref(), b"v3");
+
+        // Dropping the last handle stops the refreshes, which ends the subscription.
+        drop(url_resource);
+        assert!(time::timeout(Duration::from_secs(1), updates.next())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "notify")]
+    #[tokio::test]
+    async fn watch_invalidates() {
+        use std::os::unix::fs::symlink;
+
+        use tokio::fs::{create_dir, rename};
+
+        // Laid out like a Kubernetes ConfigMap volume.
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        create_dir(dir.join("..v1")).await.unwrap();
+        write(dir.join("..v1/config.json"), b"v1").await.unwrap();
+        symlink("..v1", dir.join("..data")).unwrap();
+        symlink("..data/config.json", dir.join("config.json")).unwrap();
+
+        let url_resource = UrlResource::new(config::UrlResource {
+            cache_ttl: Some(Duration::from_secs(60)),
+            watch: Some(true),
+            ..config::UrlResource::new(Url::from_file_path(dir.join("config.json")).unwrap())
+        })
+        .unwrap();
+        let eventually = |expected: &'static [u8]| {
+            let url_resource = url_resource.clone();
+            async move {
+                for _ in 0..100 {
+                    if url_resource.fetch().await.unwrap().data.as_ref() == expected {
+                        return;
+                    }
+                    time::sleep(Duration::from_millis(20)).await;
+                }
+                panic!("expected {expected:?}");
+            }
+        };
+        eventually(b"v1").await;
+
+        // ConfigMap update swapping the `..data` symlink.
+        create_dir(dir.join("..v2")).await.unwrap();
+        write(dir.join("..v2/config.json"), b"v2").await.unwrap();
+        symlink("..v2", dir.join("..data_tmp")).unwrap();
+        rename(dir.join("..data_tmp"), dir.join("..data"))
+            .await
+            .unwrap();
+        eventually(b"v2").await;
+
+        // Editor renaming a new file over the old one.
+        write(dir.join("config.json.tmp"), b"v3").await.unwrap();
+        rename(dir.join("config.json.tmp"), dir.join("config.json"))
+            .await
+            .unwrap();
+        eventually(b"v3").await;
+
+        // Modified in place.
+        write(dir.join("config.json"), b"v4").await.unwrap();
+        eventually(b"v4").await;
+    }
+
+    #[tokio::test]
+    async fn fetch_stream_and_max_size() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("large.bin");
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        write(&file_path, &data).await.unwrap();
+        let base_url = serve(&[(
+            "/large.bin",
+            "HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n0123456789",
+        )])
+        .await;
+        let resource = |url: Url, expected_hash: Option<String>, max_size| {
+            UrlResource::new(config::UrlResource {
+                max_size,
+                expected_hash,
+                ..config::UrlResource::new(url)
+            })
+            .unwrap()
+        };
+        let file_url = Url::from_file_path(&file_path).unwrap();
+        let expected_hash = format!("sha256:{}", sha256::digest(&data));
+
+        // Streamed in chunks regardless of the max size.
+        let mut chunks = resource(file_url.clone(), Some(expected_hash.clone()), Some(10))
+            .fetch_stream()
+            .await
+            .unwrap();
+        let mut streamed = Vec::new();
+        let mut count = 0;
+        while let Some(chunk) = chunks.next().await {
+            streamed.extend_from_slice(&chunk.unwrap());
+            count += 1;
+        }
+        assert_eq!(streamed, data);
+        assert!(count > 1);
+
+        // Ends with an error once the content is known not to match.
+        let mismatched = format!("sha256:{}", sha256::digest(b"other"));
+        let results: Vec<_> = resource(file_url.clone(), Some(mismatched), None)
+            .fetch_stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+        assert_eq!(
+            results
+                .last()
+                .unwrap()
+                .as_ref()
+                .err()
+                .map(UrlResourceErrorReason::from),
+            Some(UrlResourceErrorReason::IntegrityMismatch)
+        );
+
+        // The size is checked up front for files and while reading for HTTP without a length.
+        for (url, max_size, ok) in [
+            (file_url.clone(), 200_000, true),
+            (file_url, 199_999, false),
+            (base_url.join("large.bin").unwrap(), 10, true),
+            (base_url.join("large.bin").unwrap(), 9, false),
+        ] {
+            let result = resource(url.clone(), None, Some(max_size)).fetch().await;
+            match ok {
+                true => assert!(result.is_ok(), "{url} {max_size}"),
+                false => assert_eq!(
+                    result.err(),
+                    Some(UrlResourceError::new_resource_too_large(max_size)),
+                    "{url} {max_size}"
+                ),
+            }
+        }
     }
 
     #[traced_test]
@@ -427,12 +1350,8 @@ mod test {
         let file_path = temp_dir.path().join("hello.txt");
 
         let config = config::UrlResource {
-            url: Url::from_file_path(&file_path).unwrap(),
             cache_ttl: Some(cache_ttl_millis.to_owned()),
-            hash: None,
-            provider: config::UrlResourceProvider::Tokio(TokioUrlResourceProvider {
-                mpsc_channel_size: None,
-            }),
+            ..config::UrlResource::new(Url::from_file_path(&file_path).unwrap())
         };
 
         let url_resource = UrlResource::new(config).unwrap();
@@ -469,4 +1388,207 @@ mod test {
             file_content
         );
     }
+
+    /// Serves canned raw HTTP responses by request path, never responding to unknown paths.
+    async fn serve(routes: &[(&'static str, &'static str)]) -> Url {
+        let routes = routes.to_vec();
+        serve_with(move |request| {
+            let path = request.split(' ').nth(1).unwrap();
+            routes
+                .iter()
+                .find(|(route, _)| *route == path)
+                .map(|(_, response)| response.to_string())
+        })
+        .await
+    }
+
+    /// Serves the raw HTTP response returned for each lowercased request, never responding when
+    /// there is none.
+    async fn serve_with(handler: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let request = String::from_utf8(request).unwrap().to_lowercase();
+                    match handler(&request) {
+                        Some(response) => stream.write_all(response.as_bytes()).await.unwrap(),
+                        None => std::future::pending().await,
+                    }
+                });
+            }
+        });
+        Url::parse(&format!("http://{address}/")).unwrap()
+    }
+
+    fn http_resource(url: Url, http: config::HttpOptions) -> UrlResource {
+        UrlResource::new(config::UrlResource {
+            cache_ttl: Some(Duration::from_secs(60)),
+            hash: vec![config::UrlResourceHash::Sha256],
+            http: Some(http),
+            ..config::UrlResource::new(url)
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_resource_works() {
+        let base_url = serve(&[
+            (
+                "/hello.txt",
+                "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 14\r\nconnection: close\r\n\r\nHello, world!\n",
+            ),
+            (
+                "/moved",
+                "HTTP/1.1 302 Found\r\nlocation: /hello.txt\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ),
+            (
+                "/missing",
+                "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ),
+            (
+                "/unauthorized",
+                "HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ),
+            (
+                "/forbidden",
+                "HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ),
+            (
+                "/unavailable",
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ),
+        ])
+        .await;
+        let expected_hash =
+            "sha256:d9014c4624844aa5bac314773d6b689ad467fa4e1d1a50a1b8a99d5a95f72ff5";
+
+        for path in ["hello.txt", "moved"] {
+            let content = http_resource(base_url.join(path).unwrap(), Default::default())
+                .fetch()
+                .await
+                .unwrap();
+            assert_eq!(content.data.as_ref(), b"Hello, world!\n");
+            assert_eq!(content.digests[0].to_string(), expected_hash);
+            assert_eq!(content.metadata.url, base_url.join("hello.txt").unwrap());
+            assert_eq!(content.metadata.content_type.as_deref(), Some("text/plain"));
+            assert_eq!(content.metadata.modified, None);
+        }
+
+        let no_redirects = config::HttpOptions {
+            max_redirects: Some(0),
+            ..Default::default()
+        };
+        let short_timeout = config::HttpOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        for (path, http, reason) in [
+            (
+                "missing",
+                Default::default(),
+                UrlResourceErrorReason::ResourceNotFound,
+            ),
+            (
+                "unauthorized",
+                Default::default(),
+                UrlResourceErrorReason::ResourceAccessDenied,
+            ),
+            (
+                "forbidden",
+                Default::default(),
+                UrlResourceErrorReason::ResourceAccessDenied,
+            ),
+            (
+                "unavailable",
+                Default::default(),
+                UrlResourceErrorReason::ServiceUnavailable,
+            ),
+            (
+                "moved",
+                no_redirects,
+                UrlResourceErrorReason::ResourceReadError,
+            ),
+            (
+                "slow",
+                short_timeout,
+                UrlResourceErrorReason::ServiceUnavailable,
+            ),
+        ] {
+            let result = http_resource(base_url.join(path).unwrap(), http)
+                .fetch()
+                .await;
+            assert_eq!(
+                result.err().map(|err| UrlResourceErrorReason::from(&err)),
+                Some(reason),
+                "{path}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn http_revalidation() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base_url = serve_with({
+            let requests = requests.clone();
+            move |request| {
+                requests.lock().unwrap().push(request.to_owned());
+                let response = if request.contains("if-none-match: \"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n"
+                } else {
+                    "HTTP/1.1 200 OK\r\netag: \"v1\"\r\nlast-modified: Wed, 21 Oct 2015 07:28:00 GMT\r\ncontent-length: 14\r\nconnection: close\r\n\r\nHello, world!\n"
+                };
+                Some(response.to_owned())
+            }
+        })
+        .await;
+        let cache_ttl = Duration::from_millis(100);
+        let url_resource = UrlResource::new(config::UrlResource {
+            cache_ttl: Some(cache_ttl),
+            hash: vec![config::UrlResourceHash::Sha256],
+            ..config::UrlResource::new(base_url.join("hello.txt").unwrap())
+        })
+        .unwrap();
+
+        let fetched = url_resource.fetch().await.unwrap();
+        time::sleep(cache_ttl * 2).await;
+        let revalidated = url_resource.fetch().await.unwrap();
+        time::sleep(cache_ttl * 2).await;
+        let revalidated_again = url_resource.fetch().await.unwrap();
+
+        for content in [&revalidated, &revalidated_again] {
+            assert_eq!(content.data, fetched.data);
+            assert_eq!(content.digests, fetched.digests);
+            assert_eq!(content.metadata.etag.as_deref(), Some("\"v1\""));
+            assert_eq!(
+                content.metadata.last_modified.as_deref(),
+                Some("Wed, 21 Oct 2015 07:28:00 GMT")
+            );
+        }
+        assert!(revalidated.metadata.fetched_at > fetched.metadata.fetched_at);
+        assert!(revalidated_again.metadata.fetched_at > revalidated.metadata.fetched_at);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[0].contains("if-none-match"));
+        for request in &requests[1..] {
+            assert!(request.contains("if-none-match: \"v1\""), "{request}");
+            assert!(
+                request.contains("if-modified-since: wed, 21 oct 2015 07:28:00 gmt"),
+                "{request}"
+            );
+        }
+    }
 }
@@ -0,0 +1,148 @@
+//! Invalidates a cached `file` URL resource as soon as its file changes.
+
+use std::{
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::{fs, sync::mpsc, task::JoinHandle, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::UrlResourceCommand;
+use crate::error::UrlResourceError;
+
+/// Editors and ConfigMap updates change the directory in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Identifies the file behind a path. Symlinks are followed so that swapping a symlink, such as
+/// the `..data` symlink of a Kubernetes ConfigMap volume, is noticed just like renaming a new
+/// file over the old one.
+#[derive(Debug, PartialEq)]
+struct Fingerprint {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+async fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    let path = fs::canonicalize(path).await.ok()?;
+    let metadata = fs::metadata(&path).await.ok()?;
+    Some(Fingerprint {
+        path,
+        dev: metadata.dev(),
+        ino: metadata.ino(),
+        size: metadata.size(),
+        mtime: (metadata.mtime(), metadata.mtime_nsec()),
+        ctime: (metadata.ctime(), metadata.ctime_nsec()),
+    })
+}
+
+/// Watches the parent directory, since the file itself may be replaced, sending
+/// [UrlResourceCommand::Invalidate] whenever the file is different after a change. The watch
+/// ends once `shutdown` is cancelled or the resource is gone.
+pub(super) fn spawn(
+    path: PathBuf,
+    commands_tx: mpsc::WeakSender<UrlResourceCommand>,
+    shutdown: CancellationToken,
+) -> Result<JoinHandle<()>, UrlResourceError> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| {
+            UrlResourceError::new_failed_precondition(format!(
+                "Cannot watch `{}` without a parent directory",
+                path.display()
+            ))
+        })?
+        .to_owned();
+
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = events_tx.send(event);
+    })
+    .map_err(|err| UrlResourceError::new_failed_precondition(err.to_string()))?;
+    watcher
+        .watch(&parent, RecursiveMode::NonRecursive)
+        .map_err(|err| {
+            UrlResourceError::new_failed_precondition(format!(
+                "Cannot watch `{}`: {err}",
+                parent.display()
+            ))
+        })?;
+
+    Ok(tokio::spawn(async move {
+        let _watcher = watcher;
+        let mut current = fingerprint(&path).await;
+        loop {
+            let event = tokio::select! {
+                event = events_rx.recv() => event,
+                _ = shutdown.cancelled() => return,
+            };
+            let Some(event) = event else {
+                return;
+            };
+            let Some(commands_tx) = commands_tx.upgrade() else {
+                return;
+            };
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => continue,
+                Ok(_) => {}
+                Err(err) => warn!(error = %err, path = %path.display(), "File watch error"),
+            }
+            sleep(DEBOUNCE).await;
+            while events_rx.try_recv().is_ok() {}
+
+            let latest = fingerprint(&path).await;
+            if latest != current {
+                debug!(path = %path.display(), "File changed invalidating cache");
+                current = latest;
+                if commands_tx
+                    .send(UrlResourceCommand::Invalidate)
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::{fs, sync::mpsc, time::timeout};
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn stops_once_the_resource_is_gone() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+
+        // Stopped by the actor on shutdown.
+        let (commands_tx, _commands_rx) = mpsc::channel(1);
+        let shutdown = CancellationToken::new();
+        let task = super::spawn(path.clone(), commands_tx.downgrade(), shutdown.clone()).unwrap();
+        shutdown.cancel();
+        timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Stopped by the next change once every command sender is dropped.
+        let shutdown = CancellationToken::new();
+        let task = super::spawn(path.clone(), commands_tx.downgrade(), shutdown).unwrap();
+        drop(commands_tx);
+        fs::write(&path, b"v1").await.unwrap();
+        timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}